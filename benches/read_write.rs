@@ -0,0 +1,65 @@
+// Measures reading and writing of the bigtest fixture and of a synthetic chunk, reporting the
+// throughput over the uncompressed bytes. Run with `cargo bench`.
+#[macro_use]
+extern crate criterion;
+extern crate rust_nbt;
+
+use criterion::{black_box, Criterion, Throughput};
+use rust_nbt::file::{NBTFile, NBTFileBuilder};
+use rust_nbt::write;
+use rust_nbt::NBTTag;
+
+const BIGTEST: &[u8] = include_bytes!("../tests/data/bigtest.nbt");
+
+// A chunk with 24 sections of block states and two heightmaps, roughly the size of one from 1.18+
+fn synthetic_chunk() -> NBTFile {
+    let sections = (0..24).map(|y| {
+        let mut section = NBTTag::TagCompound(Default::default());
+        section.insert("Y", NBTTag::TagByte(y - 4));
+        section.insert("data", NBTTag::TagLongArray((0..256).map(|i| i * 31 + i64::from(y)).collect()));
+        section.insert("palette", NBTTag::TagList(vec![NBTTag::TagString("minecraft:stone".to_owned()); 16]));
+        section
+    });
+
+    NBTFileBuilder::new()
+        .put("DataVersion", 3465)
+        .put("xPos", 0)
+        .put("zPos", 0)
+        .put("sections", NBTTag::TagList(sections.collect()))
+        .put("MOTION_BLOCKING", NBTTag::TagLongArray(vec![0x0101_0101; 37]))
+        .put("WORLD_SURFACE", NBTTag::TagLongArray(vec![0x0202_0202; 37]))
+        .build()
+}
+
+// Reading and writing of a whole uncompressed file, as a group named after it
+fn bench_file(c: &mut Criterion, name: &str, bytes: &[u8]) {
+    let file = NBTFile::from_bytes(bytes).unwrap();
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+
+    group.bench_function("read", |b| b.iter(|| NBTFile::from_bytes(black_box(bytes)).unwrap()));
+    group.bench_function("write", |b| b.iter(|| black_box(&file).as_bytes().unwrap()));
+    group.finish();
+}
+
+fn bench_bigtest(c: &mut Criterion) {
+    bench_file(c, "bigtest", BIGTEST);
+}
+
+fn bench_chunk(c: &mut Criterion) {
+    bench_file(c, "chunk", &synthetic_chunk().as_bytes().unwrap());
+}
+
+// The block states of a full 1.16+ chunk at 4 bits per block, which the writer reserves the whole
+// of before writing the longs
+fn bench_long_array(c: &mut Criterion) {
+    let array = NBTTag::TagLongArray((0..16 * 256).collect());
+    let mut group = c.benchmark_group("long array");
+    group.throughput(Throughput::Bytes(array.serialized_len() as u64));
+
+    group.bench_function("write", |b| b.iter(|| write::write_tag(black_box(&array), false, false, None).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_bigtest, bench_chunk, bench_long_array);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+extern crate rust_nbt;
+
+use rust_nbt::file::NBTFileBuilder;
+use rust_nbt::{IndexMap, NBTTag};
+
+// Builds a minimal level.dat-style file and prints its size
+fn main() {
+    let mut game_rules = IndexMap::new();
+    game_rules.insert("doDaylightCycle".to_owned(), NBTTag::from("true"));
+
+    let mut data = IndexMap::new();
+    data.insert("LevelName".to_owned(), NBTTag::from("New World"));
+    data.insert("DataVersion".to_owned(), NBTTag::from(3465));
+    data.insert("RandomSeed".to_owned(), NBTTag::from(-4_172_144_997_902_289_642i64));
+    data.insert("SpawnX".to_owned(), NBTTag::from(0));
+    data.insert("SpawnY".to_owned(), NBTTag::from(64));
+    data.insert("SpawnZ".to_owned(), NBTTag::from(0));
+    data.insert("GameRules".to_owned(), NBTTag::from(game_rules));
+
+    let file = NBTFileBuilder::new()
+        .put("Data", data)
+        .build();
+
+    let bytes = file.as_bytes().expect("level.dat could not be written");
+
+    println!("level.dat is {} bytes uncompressed", bytes.len());
+}
@@ -0,0 +1,43 @@
+extern crate rust_nbt;
+
+use rust_nbt::{from_snbt, FromNbt, IndexMap, NBTError, NBTTag, ToNbt};
+
+// A few fields of the player data in playerdata/<uuid>.dat
+#[derive(Debug, PartialEq)]
+struct Player {
+    health: f32,
+    pos: Vec<f64>,
+    on_ground: bool,
+    xp_level: i32,
+}
+
+impl FromNbt for Player {
+    fn from_nbt(tag: &NBTTag) -> Result<Player, NBTError> {
+        Ok(Player {
+            health: tag.field("Health")?,
+            pos: tag.field("Pos")?,
+            on_ground: tag.field("OnGround")?,
+            xp_level: tag.field("XpLevel")?,
+        })
+    }
+}
+
+impl ToNbt for Player {
+    fn to_nbt(&self) -> NBTTag {
+        let mut map = IndexMap::new();
+        map.insert("Health".to_owned(), self.health.to_nbt());
+        map.insert("Pos".to_owned(), self.pos.to_nbt());
+        map.insert("OnGround".to_owned(), self.on_ground.to_nbt());
+        map.insert("XpLevel".to_owned(), self.xp_level.to_nbt());
+        NBTTag::TagCompound(map)
+    }
+}
+
+// Reads a player from SNBT and converts it back
+fn main() {
+    let tag = from_snbt("{Health:20.0f,Pos:[8.5d,64.0d,-3.5d],OnGround:1b,XpLevel:30}").expect("SNBT is invalid");
+    let player = Player::from_nbt(&tag).expect("player data is incomplete");
+
+    println!("{:?}", player);
+    assert_eq!(player.to_nbt(), tag);
+}
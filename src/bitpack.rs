@@ -0,0 +1,109 @@
+// Helpers for the bit-packed unsigned values stored in TagLongArrays, such as heightmaps and
+// block state indices in chunk sections
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Packing {
+    // Entries are packed back to back and may span two longs (before 1.16)
+    Spanning,
+    // Entries never span two longs, leaving the remaining high bits of each long unused (1.16+)
+    Aligned,
+}
+
+// Number of longs needed to store entry_count entries of bits_per_entry bits
+pub fn packed_len(bits_per_entry: usize, entry_count: usize, mode: Packing) -> usize {
+    match mode {
+        Packing::Spanning => (entry_count * bits_per_entry).div_ceil(64),
+        Packing::Aligned => entry_count.div_ceil(64 / bits_per_entry),
+    }
+}
+
+// Unpacks entry_count values of bits_per_entry bits (1 to 32) each. Longs missing from data are
+// read as zero, so compare data.len() against packed_len to detect truncated arrays.
+pub fn unpack_longs(data: &[i64], bits_per_entry: usize, entry_count: usize, mode: Packing) -> Vec<u32> {
+    assert!(bits_per_entry > 0 && bits_per_entry <= 32, "bits_per_entry must be between 1 and 32");
+
+    let mask = (1u64 << bits_per_entry) - 1;
+    let long_at = |index: usize| data.get(index).map_or(0, |&long| long as u64);
+    let mut output = Vec::with_capacity(entry_count);
+
+    for entry in 0..entry_count {
+        let (index, offset) = position(entry, bits_per_entry, mode);
+        let mut value = long_at(index) >> offset;
+
+        if offset + bits_per_entry > 64 {
+            value |= long_at(index + 1) << (64 - offset);
+        }
+
+        output.push((value & mask) as u32);
+    }
+
+    output
+}
+
+// Packs values into longs using bits_per_entry bits (1 to 32) per value, the inverse of
+// unpack_longs. Bits of values that don't fit in bits_per_entry are dropped.
+pub fn pack(values: &[u32], bits_per_entry: usize, mode: Packing) -> Vec<i64> {
+    assert!(bits_per_entry > 0 && bits_per_entry <= 32, "bits_per_entry must be between 1 and 32");
+
+    let mask = (1u64 << bits_per_entry) - 1;
+    let mut output = vec![0u64; packed_len(bits_per_entry, values.len(), mode)];
+
+    for (entry, &value) in values.iter().enumerate() {
+        let (index, offset) = position(entry, bits_per_entry, mode);
+        let value = u64::from(value) & mask;
+
+        output[index] |= value << offset;
+
+        if offset + bits_per_entry > 64 {
+            output[index + 1] |= value >> (64 - offset);
+        }
+    }
+
+    output.into_iter().map(|long| long as i64).collect()
+}
+
+// Index of the long an entry starts in, and the bit offset within that long
+fn position(entry: usize, bits_per_entry: usize, mode: Packing) -> (usize, usize) {
+    match mode {
+        Packing::Spanning => {
+            let bit = entry * bits_per_entry;
+            (bit / 64, bit % 64)
+        }
+        Packing::Aligned => {
+            let per_long = 64 / bits_per_entry;
+            (entry / per_long, (entry % per_long) * bits_per_entry)
+        }
+    }
+}
+
+#[test]
+fn test_unpack_spanning() {
+    // Five 9 bit entries fill 45 bits; eight entries span into the second long
+    let values: Vec<u32> = vec![1, 2, 3, 511, 256, 0, 7, 300];
+    let packed = pack(&values, 9, Packing::Spanning);
+
+    assert_eq!(packed.len(), 2);
+    assert_eq!(packed[0] as u64 & 0x1FF, 1);
+    assert_eq!((packed[0] as u64 >> 63) | ((packed[1] as u64 & 0xFF) << 1), 300);
+    assert_eq!(unpack_longs(&packed, 9, values.len(), Packing::Spanning), values);
+}
+
+#[test]
+fn test_unpack_aligned() {
+    // Seven 9 bit entries fit in a long, leaving the upper bit unused
+    let values: Vec<u32> = (0..37).map(|value| value * 13).collect();
+    let packed = pack(&values, 9, Packing::Aligned);
+
+    assert_eq!(packed.len(), 6);
+    assert_eq!(packed[1] as u64 & 0x1FF, 7 * 13);
+    assert_eq!(unpack_longs(&packed, 9, values.len(), Packing::Aligned), values);
+}
+
+#[test]
+fn test_unpack_full_width() {
+    let values: Vec<u32> = vec![u32::MAX, 0, 12345678];
+
+    for mode in &[Packing::Spanning, Packing::Aligned] {
+        assert_eq!(unpack_longs(&pack(&values, 32, *mode), 32, 3, *mode), values);
+    }
+}
@@ -0,0 +1,131 @@
+// Helpers for reading the contents of chunks and their sections, like stored in region files
+
+use bitpack::{self, Packing};
+use error::NBTError;
+use write::get_tag_id;
+use NBTTag;
+
+// Number of blocks in a 16x16x16 chunk section
+const SECTION_VOLUME: usize = 4096;
+
+// Decodes the block_states of a chunk section (1.18+) into the palette index of each of its 4096
+// blocks, in YZX order. The indices are packed into the data TagLongArray with at least 4 bits per
+// entry, enough to index the palette. data is left out when the palette only has one entry.
+pub fn decode_block_states(section: &NBTTag) -> Result<Vec<u16>, NBTError> {
+    let block_states = section.get("block_states")
+        .ok_or_else(|| NBTError::InvalidValue("Section has no block_states".to_owned()))?;
+
+    let palette_len = match block_states.get("palette") {
+        Some(NBTTag::TagList(palette)) if !palette.is_empty() => palette.len(),
+        Some(NBTTag::TagList(_)) | None => return Err(NBTError::InvalidValue("Section has no palette".to_owned())),
+        Some(other) => return Err(NBTError::UnexpectedType { expected: 9, found: get_tag_id(other) }),
+    };
+
+    let data = match block_states.get("data") {
+        Some(NBTTag::TagLongArray(data)) => data,
+        None if palette_len == 1 => return Ok(vec![0; SECTION_VOLUME]),
+        None => return Err(NBTError::InvalidValue("Section has no block state data".to_owned())),
+        Some(other) => return Err(NBTError::UnexpectedType { expected: 12, found: get_tag_id(other) }),
+    };
+
+    let bits_per_entry = bits_for(palette_len).max(4);
+    let expected_len = bitpack::packed_len(bits_per_entry, SECTION_VOLUME, Packing::Aligned);
+
+    if data.len() != expected_len {
+        return Err(NBTError::InvalidValue(format!(
+            "Block state data has {} longs, but a palette of {} entries requires {}",
+            data.len(), palette_len, expected_len)));
+    }
+
+    Ok(bitpack::unpack_longs(data, bits_per_entry, SECTION_VOLUME, Packing::Aligned)
+        .into_iter()
+        .map(|index| index as u16)
+        .collect())
+}
+
+// The block entities of a chunk, like chests and signs. Chunks written before 1.18 keep them in
+// Level as TileEntities.
+pub fn block_entities(chunk: &NBTTag) -> Option<&[NBTTag]> {
+    list_at(chunk, &[&["block_entities"], &["Level", "TileEntities"]])
+}
+
+// The entities of a chunk. Since 1.17 these are stored in separate entity region files, as
+// Entities at the root, while older chunks keep them in Level and proto-chunks as entities.
+pub fn entities(chunk: &NBTTag) -> Option<&[NBTTag]> {
+    list_at(chunk, &[&["entities"], &["Entities"], &["Level", "Entities"]])
+}
+
+// Returns the first list found at one of the paths of keys
+fn list_at<'a>(chunk: &'a NBTTag, paths: &[&[&str]]) -> Option<&'a [NBTTag]> {
+    paths.iter().find_map(|path| {
+        match path.iter().try_fold(chunk, |tag, key| tag.get(key)) {
+            Some(NBTTag::TagList(list)) => Some(&list[..]),
+            _ => None,
+        }
+    })
+}
+
+// Number of bits needed to store the indices into a palette of len entries
+fn bits_for(len: usize) -> usize {
+    (usize::BITS - (len - 1).leading_zeros()) as usize
+}
+
+#[cfg(test)]
+fn section(palette_len: usize, data: Option<Vec<i64>>) -> NBTTag {
+    use indexmap::IndexMap;
+
+    let palette = (0..palette_len).map(|i| NBTTag::TagString(format!("minecraft:block_{}", i))).collect();
+
+    let mut block_states = NBTTag::TagCompound(IndexMap::new());
+    block_states.insert("palette", NBTTag::TagList(palette));
+
+    if let Some(data) = data {
+        block_states.insert("data", NBTTag::TagLongArray(data));
+    }
+
+    let mut section = NBTTag::TagCompound(IndexMap::new());
+    section.insert("block_states", block_states);
+    section
+}
+
+#[test]
+fn test_decode_block_states() {
+    let indices: Vec<u32> = (0..SECTION_VOLUME as u32).map(|i| i % 17).collect();
+    let data = bitpack::pack(&indices, 5, Packing::Aligned);
+
+    let decoded = decode_block_states(&section(17, Some(data))).unwrap();
+    assert_eq!(decoded.len(), SECTION_VOLUME);
+    assert!(decoded.iter().zip(&indices).all(|(&a, &b)| u32::from(a) == b));
+
+    // Small palettes still use 4 bits per entry
+    let data = bitpack::pack(&[1; SECTION_VOLUME], 4, Packing::Aligned);
+    assert_eq!(decode_block_states(&section(2, Some(data))).unwrap(), vec![1; SECTION_VOLUME]);
+
+    assert_eq!(decode_block_states(&section(1, None)).unwrap(), vec![0; SECTION_VOLUME]);
+}
+
+#[test]
+fn test_decode_block_states_inconsistent_length() {
+    let data = bitpack::pack(&[0; SECTION_VOLUME], 4, Packing::Aligned);
+
+    assert_eq!(decode_block_states(&section(17, Some(data))).unwrap_err().to_string(),
+               "Block state data has 256 longs, but a palette of 17 entries requires 342");
+    assert!(decode_block_states(&section(17, None)).is_err());
+    assert!(decode_block_states(&NBTTag::TagInt(1)).is_err());
+}
+
+#[test]
+fn test_block_entities_and_entities() {
+    use from_snbt;
+
+    let chunk = from_snbt("{block_entities:[{id:\"minecraft:chest\"}],entities:[{id:\"minecraft:pig\"},{id:\"minecraft:cow\"}]}").unwrap();
+    assert_eq!(block_entities(&chunk).map(|list| list.len()), Some(1));
+    assert_eq!(entities(&chunk).map(|list| list.len()), Some(2));
+
+    let old = from_snbt("{Level:{TileEntities:[],Entities:[{id:\"minecraft:pig\"}]}}").unwrap();
+    assert_eq!(block_entities(&old), Some(&[][..]));
+    assert_eq!(entities(&old).map(|list| list.len()), Some(1));
+
+    assert_eq!(block_entities(&from_snbt("{block_entities:1}").unwrap()), None);
+    assert_eq!(entities(&NBTTag::TagInt(1)), None);
+}
@@ -0,0 +1,117 @@
+#[cfg(feature = "io")]
+use error::NBTError;
+#[cfg(feature = "io")]
+use flate2::read::{GzDecoder, ZlibDecoder};
+#[cfg(feature = "io")]
+use flate2::write::{GzEncoder, ZlibEncoder};
+#[cfg(feature = "io")]
+use std::io::prelude::*;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zlib,
+}
+
+// Guesses the compression of a file from its first bytes. Uncompressed files start with the id of
+// their root tag, which never collides with the gzip magic number or a zlib header.
+pub fn detect(bytes: &[u8]) -> Compression {
+    match bytes {
+        [0x1F, 0x8B, ..] => Compression::Gzip,
+        [0x78, flags, ..] if (0x7800 | u16::from(*flags)) % 31 == 0 => Compression::Zlib,
+        _ => Compression::None,
+    }
+}
+
+#[cfg(feature = "io")]
+pub fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, NBTError> {
+    let mut output = Vec::new();
+
+    match compression {
+        Compression::None => output.extend_from_slice(bytes),
+        Compression::Gzip => {
+            GzDecoder::new(bytes).read_to_end(&mut output)?;
+        }
+        Compression::Zlib => {
+            ZlibDecoder::new(bytes).read_to_end(&mut output)?;
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(feature = "io")]
+pub fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, NBTError> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), ::flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), ::flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+// Number of bytes compress would return, computed without keeping the compressed output around
+#[cfg(feature = "io")]
+pub fn compressed_len(bytes: &[u8], compression: Compression) -> Result<usize, NBTError> {
+    match compression {
+        Compression::None => Ok(bytes.len()),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(CountingWriter(0), ::flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?.0)
+        }
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(CountingWriter(0), ::flate2::Compression::default());
+            encoder.write_all(bytes)?;
+            Ok(encoder.finish()?.0)
+        }
+    }
+}
+
+// Discards everything written to it, counting the bytes
+#[cfg(feature = "io")]
+struct CountingWriter(usize);
+
+#[cfg(feature = "io")]
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> ::std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "io")]
+#[test]
+fn test_compression_round_trip() {
+    let input = b"Hello World!".to_vec();
+
+    for compression in &[Compression::None, Compression::Gzip, Compression::Zlib] {
+        let compressed = compress(&input, *compression).unwrap();
+        assert_eq!(decompress(&compressed, *compression).unwrap(), input);
+        assert_eq!(compressed_len(&input, *compression).unwrap(), compressed.len());
+    }
+}
+
+#[cfg(feature = "io")]
+#[test]
+fn test_detect() {
+    let input = vec![0x0A, 0x00, 0x00, 0x00];
+
+    for compression in &[Compression::None, Compression::Gzip, Compression::Zlib] {
+        assert_eq!(detect(&compress(&input, *compression).unwrap()), *compression);
+    }
+
+    assert_eq!(detect(&[]), Compression::None);
+}
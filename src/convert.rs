@@ -0,0 +1,189 @@
+// Conversion between tags and Rust types by hand, for types where going through serde_json is too
+// slow or not wanted. Implement both traits to read and write a struct as a compound.
+
+use error::NBTError;
+use NBTTag;
+use TagType;
+
+pub trait FromNbt: Sized {
+    fn from_nbt(tag: &NBTTag) -> Result<Self, NBTError>;
+
+    // Like from_nbt, but accepting an integer tag of another type than expected when its value
+    // fits, for saves in which a field changed integer type between versions. Only the integer
+    // conversions are lenient, all others are as strict as from_nbt.
+    fn from_nbt_lenient(tag: &NBTTag) -> Result<Self, NBTError> {
+        Self::from_nbt(tag)
+    }
+}
+
+pub trait ToNbt {
+    fn to_nbt(&self) -> NBTTag;
+}
+
+impl NBTTag {
+    // Converts the entry key of a TagCompound, which is required to be present
+    pub fn field<T: FromNbt>(&self, key: &str) -> Result<T, NBTError> {
+        self.field_with(key, T::from_nbt)
+    }
+
+    // Like field, but converting with FromNbt::from_nbt_lenient
+    pub fn field_lenient<T: FromNbt>(&self, key: &str) -> Result<T, NBTError> {
+        self.field_with(key, T::from_nbt_lenient)
+    }
+
+    fn field_with<T, F: Fn(&NBTTag) -> Result<T, NBTError>>(&self, key: &str, convert: F) -> Result<T, NBTError> {
+        match *self {
+            NBTTag::TagCompound(ref map) => match map.get(key) {
+                Some(value) => convert(value),
+                None => Err(NBTError::InvalidValue(format!("Compound has no key {:?}", key))),
+            },
+            ref other => Err(NBTError::UnexpectedType { expected: 10, found: other.tag_type().into() }),
+        }
+    }
+}
+
+macro_rules! convert_value (($t:ty, $variant:ident, $id:expr) => (
+    impl FromNbt for $t {
+        fn from_nbt(tag: &NBTTag) -> Result<$t, NBTError> {
+            match *tag {
+                NBTTag::$variant(ref value) => Ok(value.clone()),
+                ref other => Err(NBTError::UnexpectedType { expected: $id, found: other.tag_type().into() }),
+            }
+        }
+    }
+
+    impl ToNbt for $t {
+        fn to_nbt(&self) -> NBTTag {
+            NBTTag::$variant(self.clone())
+        }
+    }
+););
+
+macro_rules! convert_int (($t:ty, $variant:ident, $tag_type:ident, $id:expr) => (
+    impl FromNbt for $t {
+        fn from_nbt(tag: &NBTTag) -> Result<$t, NBTError> {
+            match *tag {
+                NBTTag::$variant(value) => Ok(value),
+                ref other => Err(NBTError::UnexpectedType { expected: $id, found: other.tag_type().into() }),
+            }
+        }
+
+        fn from_nbt_lenient(tag: &NBTTag) -> Result<$t, NBTError> {
+            <$t>::from_nbt(&tag.coerce_int(TagType::$tag_type)?)
+        }
+    }
+
+    impl ToNbt for $t {
+        fn to_nbt(&self) -> NBTTag {
+            NBTTag::$variant(*self)
+        }
+    }
+););
+
+convert_int!(i8, TagByte, Byte, 1);
+convert_int!(i16, TagShort, Short, 2);
+convert_int!(i32, TagInt, Int, 3);
+convert_int!(i64, TagLong, Long, 4);
+convert_value!(f32, TagFloat, 5);
+convert_value!(f64, TagDouble, 6);
+convert_value!(String, TagString, 8);
+
+// Booleans are stored as a TagByte of 0 or 1, like the game does. Any other value counts as true.
+impl FromNbt for bool {
+    fn from_nbt(tag: &NBTTag) -> Result<bool, NBTError> {
+        i8::from_nbt(tag).map(|value| value != 0)
+    }
+
+    fn from_nbt_lenient(tag: &NBTTag) -> Result<bool, NBTError> {
+        i8::from_nbt_lenient(tag).map(|value| value != 0)
+    }
+}
+
+impl ToNbt for bool {
+    fn to_nbt(&self) -> NBTTag {
+        NBTTag::TagByte(*self as i8)
+    }
+}
+
+impl FromNbt for NBTTag {
+    fn from_nbt(tag: &NBTTag) -> Result<NBTTag, NBTError> {
+        Ok(tag.clone())
+    }
+}
+
+impl ToNbt for NBTTag {
+    fn to_nbt(&self) -> NBTTag {
+        self.clone()
+    }
+}
+
+// Vecs are read from a TagList, or from an array for the element types of arrays
+impl<T: FromNbt> FromNbt for Vec<T> {
+    fn from_nbt(tag: &NBTTag) -> Result<Vec<T>, NBTError> {
+        vec_from_nbt(tag, T::from_nbt)
+    }
+
+    fn from_nbt_lenient(tag: &NBTTag) -> Result<Vec<T>, NBTError> {
+        vec_from_nbt(tag, T::from_nbt_lenient)
+    }
+}
+
+fn vec_from_nbt<T, F: Fn(&NBTTag) -> Result<T, NBTError>>(tag: &NBTTag, convert: F) -> Result<Vec<T>, NBTError> {
+    match *tag {
+        NBTTag::TagList(ref list) => list.iter().map(convert).collect(),
+        NBTTag::TagByteArray(ref values) => values.iter().map(|&value| convert(&NBTTag::TagByte(value))).collect(),
+        NBTTag::TagIntArray(ref values) => values.iter().map(|&value| convert(&NBTTag::TagInt(value))).collect(),
+        NBTTag::TagLongArray(ref values) => values.iter().map(|&value| convert(&NBTTag::TagLong(value))).collect(),
+        ref other => Err(NBTError::UnexpectedType { expected: 9, found: other.tag_type().into() }),
+    }
+}
+
+// Vecs are always written as a TagList; use NBTTag::from for arrays
+impl<T: ToNbt> ToNbt for Vec<T> {
+    fn to_nbt(&self) -> NBTTag {
+        NBTTag::TagList(self.iter().map(ToNbt::to_nbt).collect())
+    }
+}
+
+#[test]
+fn test_convert_values() {
+    assert_eq!(i32::from_nbt(&NBTTag::TagInt(3)).unwrap(), 3);
+    assert_eq!(String::from_nbt(&"a".to_owned().to_nbt()).unwrap(), "a");
+    assert!(bool::from_nbt(&true.to_nbt()).unwrap());
+    assert!(i32::from_nbt(&NBTTag::TagLong(3)).is_err());
+
+    assert_eq!(Vec::<i32>::from_nbt(&NBTTag::TagIntArray(vec![1, 2])).unwrap(), vec![1, 2]);
+    assert_eq!(vec![1.5f64].to_nbt(), NBTTag::TagList(vec![NBTTag::TagDouble(1.5)]));
+    assert_eq!(Vec::<f64>::from_nbt(&vec![1.5f64].to_nbt()).unwrap(), vec![1.5]);
+    assert!(Vec::<i8>::from_nbt(&NBTTag::TagIntArray(vec![1])).is_err());
+}
+
+#[test]
+fn test_field() {
+    use from_snbt;
+
+    let tag = from_snbt("{name:\"Steve\",Health:20.0f}").unwrap();
+
+    assert_eq!(tag.field::<String>("name").unwrap(), "Steve");
+    assert_eq!(tag.field::<f32>("Health").unwrap(), 20.0);
+    assert_eq!(tag.field::<i32>("Health").unwrap_err().to_string(), "Expected tag of type TAG_Int (3) but found TAG_Float (5)");
+    assert!(tag.field::<String>("missing").is_err());
+    assert!(NBTTag::TagInt(1).field::<i32>("a").is_err());
+}
+
+#[test]
+fn test_convert_lenient() {
+    use from_snbt;
+
+    let tag = from_snbt("{Time:20,Count:3L,Big:300s,Pos:[B;1b,2b],Flag:1s,Name:\"a\"}").unwrap();
+
+    // Strict by default
+    assert!(tag.field::<i64>("Time").is_err());
+
+    assert_eq!(tag.field_lenient::<i64>("Time").unwrap(), 20);
+    assert_eq!(tag.field_lenient::<i8>("Count").unwrap(), 3);
+    assert_eq!(tag.field_lenient::<i8>("Big").unwrap_err().to_string(), "300 does not fit in a TAG_Byte");
+    assert_eq!(tag.field_lenient::<Vec<i32>>("Pos").unwrap(), vec![1, 2]);
+    assert!(tag.field_lenient::<bool>("Flag").unwrap());
+    assert!(tag.field_lenient::<i32>("Name").is_err());
+}
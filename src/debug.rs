@@ -0,0 +1,97 @@
+// Helpers for diagnosing input that fails to parse
+use error::NBTError;
+use std::fmt::Write;
+
+// Renders the bytes within window bytes of offset as rows of 16 in hex and ASCII, like xxd, with
+// the byte at offset marked by a line below its row. NBTError::display_with_context uses this to
+// show what a parse error is about.
+pub fn dump_around(bytes: &[u8], offset: usize, window: usize) -> String {
+    let start = offset.saturating_sub(window) / 16 * 16;
+    let end = bytes.len().min(offset.saturating_add(window).saturating_add(1));
+    let mut output = String::new();
+
+    let last = end.max(offset.min(bytes.len()) + 1);
+
+    for row in (start..last).step_by(16) {
+        let _ = write!(output, "{:08x} ", row);
+
+        for index in row..row + 16 {
+            match bytes.get(index) {
+                Some(byte) if index < end => { let _ = write!(output, " {:02x}", byte); }
+                _ => output.push_str("   "),
+            }
+        }
+
+        output.push_str("  |");
+        for &byte in bytes.iter().take(end).skip(row).take(16) {
+            output.push(if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' });
+        }
+        output.push_str("|\n");
+
+        if offset >= row && offset < row + 16 {
+            output.push_str(&" ".repeat(10 + 3 * (offset - row)));
+            output.push_str("^^\n");
+        }
+    }
+
+    output
+}
+
+impl NBTError {
+    // The position in the input at which a Parse or InvalidSnbt error occurred
+    pub fn offset(&self) -> Option<usize> {
+        match *self {
+            NBTError::Parse { offset, .. } | NBTError::InvalidSnbt { offset, .. } => Some(offset),
+            _ => None,
+        }
+    }
+
+    // The message of the error followed by a dump of the 32 bytes around offset, for errors that
+    // have one. bytes are required to be the input the error was raised for, which for InvalidSnbt
+    // is the SNBT text.
+    pub fn display_with_context(&self, bytes: &[u8]) -> String {
+        match self.offset() {
+            Some(offset) => format!("{}\n{}", self, dump_around(bytes, offset, 32)),
+            None => self.to_string(),
+        }
+    }
+}
+
+#[test]
+fn test_dump_around() {
+    let bytes: Vec<u8> = (0x40..0x58).collect();
+
+    assert_eq!(dump_around(&bytes, 17, 2), format!(
+        "00000000  40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|\n\
+         00000010  50 51 52 53                                      |PQRS|\n{}^^\n", " ".repeat(13)));
+}
+
+#[test]
+fn test_dump_parse_error() {
+    use read::read_headless_nbt;
+
+    let bytes = [0x0A, 0x08, 0x00, 0x01, 0x61, 0x00, 0x05, 0x62];
+    let err = read_headless_nbt(&bytes).unwrap_err();
+
+    assert_eq!(err.offset(), Some(8));
+    assert_eq!(dump_around(&bytes, 8, 4), format!(
+        "00000000  0a 08 00 01 61 00 05 62                          |....a..b|\n{}^^\n", " ".repeat(34)));
+}
+
+#[test]
+fn test_display_with_context() {
+    use from_snbt;
+    use read::read_headless_nbt;
+
+    let bytes = [0x0A, 0x08, 0x00, 0x01, 0x61, 0x00, 0x05, 0x62];
+    assert_eq!(read_headless_nbt(&bytes).unwrap_err().display_with_context(&bytes), format!(
+        "Parse failed at byte 8 while reading TAG_String\n\
+         00000000  0a 08 00 01 61 00 05 62                          |....a..b|\n{}^^\n", " ".repeat(34)));
+
+    let snbt = "{a:1,b}";
+    assert_eq!(from_snbt(snbt).unwrap_err().display_with_context(snbt.as_bytes()), format!(
+        "Invalid SNBT at position 6: Expected ':' after key\n\
+         00000000  7b 61 3a 31 2c 62 7d                             |{{a:1,b}}|\n{}^^\n", " ".repeat(28)));
+
+    assert_eq!(NBTError::TrailingBytes(1).display_with_context(&bytes), "Found 1 bytes after the root tag");
+}
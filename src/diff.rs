@@ -0,0 +1,92 @@
+use path;
+use NBTTag;
+
+// A single difference between two trees, addressed by the path of the tag that differs
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change<'a> {
+    Added(String, &'a NBTTag),
+    Removed(String, &'a NBTTag),
+    Modified(String, &'a NBTTag, &'a NBTTag),
+}
+
+// Lists the changes needed to turn a into b. Compounds are compared key by key and lists index by
+// index; any other pair of tags that differ in type or value is reported as modified.
+pub fn diff<'a>(a: &'a NBTTag, b: &'a NBTTag) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+
+    diff_at(a, b, "", &mut changes);
+
+    changes
+}
+
+fn diff_at<'a>(a: &'a NBTTag, b: &'a NBTTag, current: &str, changes: &mut Vec<Change<'a>>) {
+    match (a, b) {
+        (NBTTag::TagCompound(a_map), NBTTag::TagCompound(b_map)) => {
+            for (key, a_value) in a_map {
+                let child = path::child_key(current, key);
+
+                match b_map.get(key) {
+                    Some(b_value) => diff_at(a_value, b_value, &child, changes),
+                    None => changes.push(Change::Removed(child, a_value)),
+                }
+            }
+
+            for (key, b_value) in b_map {
+                if !a_map.contains_key(key) {
+                    changes.push(Change::Added(path::child_key(current, key), b_value));
+                }
+            }
+        }
+        (NBTTag::TagList(a_list), NBTTag::TagList(b_list)) => {
+            for (index, a_value) in a_list.iter().enumerate() {
+                let child = path::child_index(current, index);
+
+                match b_list.get(index) {
+                    Some(b_value) => diff_at(a_value, b_value, &child, changes),
+                    None => changes.push(Change::Removed(child, a_value)),
+                }
+            }
+
+            for (index, b_value) in b_list.iter().enumerate().skip(a_list.len()) {
+                changes.push(Change::Added(path::child_index(current, index), b_value));
+            }
+        }
+        _ => {
+            if a != b {
+                changes.push(Change::Modified(current.to_owned(), a, b));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_diff_nested() {
+    use indexmap::IndexMap;
+
+    let mut item = IndexMap::new();
+    item.insert("id".to_owned(), NBTTag::TagString("minecraft:stone".to_owned()));
+    item.insert("Count".to_owned(), NBTTag::TagByte(1));
+
+    let mut before = IndexMap::new();
+    before.insert("Name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    before.insert("Inventory".to_owned(), NBTTag::TagList(vec![NBTTag::TagCompound(item.clone())]));
+
+    let mut changed_item = item.clone();
+    changed_item.insert("Count".to_owned(), NBTTag::TagByte(2));
+
+    let mut after = IndexMap::new();
+    after.insert("Inventory".to_owned(), NBTTag::TagList(vec![NBTTag::TagCompound(changed_item),
+                                                              NBTTag::TagCompound(item)]));
+    after.insert("Score".to_owned(), NBTTag::TagInt(3));
+
+    let a = NBTTag::TagCompound(before);
+    let b = NBTTag::TagCompound(after);
+    let changes = diff(&a, &b);
+
+    assert_eq!(changes.len(), 4);
+    assert_eq!(changes[0], Change::Removed("Name".to_owned(), &NBTTag::TagString("Steve".to_owned())));
+    assert_eq!(changes[1], Change::Modified("Inventory[0].Count".to_owned(), &NBTTag::TagByte(1), &NBTTag::TagByte(2)));
+    assert!(if let Change::Added(ref at, _) = changes[2] { at == "Inventory[1]" } else { false });
+    assert_eq!(changes[3], Change::Added("Score".to_owned(), &NBTTag::TagInt(3)));
+    assert!(diff(&a, &a).is_empty());
+}
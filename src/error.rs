@@ -0,0 +1,85 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use tag_id_name;
+
+#[derive(Debug)]
+pub enum NBTError {
+    Io(io::Error),
+    // The input is not valid NBT. offset is the position in the input at which reading failed and tag
+    // the type of the innermost tag that was being read, if known.
+    Parse { offset: usize, tag: Option<u8> },
+    // This many bytes follow the root of a file, while ReadOptions::reject_trailing was set
+    TrailingBytes(usize),
+    // A compound contains the key more than once, while DuplicateKey::Error was requested
+    DuplicateKey(String),
+    // The root of a file is required to be a TagCompound, but found is the id of its type
+    InvalidRoot { found: u8 },
+    // A tag of the type with id expected was required, but one with id found was given
+    UnexpectedType { expected: u8, found: u8 },
+    // The length of a string, name, array or list is too large to be written
+    TooLong { kind: &'static str, len: usize, max: usize },
+    // An array or list of the given kind has a negative length, which would overflow when cast
+    NegativeLength { kind: &'static str, len: i32 },
+    // The input ends after read of the expected elements of an array of the given kind
+    UnexpectedEof { kind: &'static str, read: usize, expected: usize },
+    // Reading requires allocating at least total bytes, more than ReadOptions::max_total_bytes
+    BudgetExceeded { total: usize, max: usize },
+    // The value can't be represented, either in NBT or in the requested format
+    InvalidValue(String),
+    // The input is not valid SNBT; offset is the byte position at which parsing stopped
+    InvalidSnbt { offset: usize, msg: &'static str },
+    UnknownCompression(u8),
+    InvalidRegion(String),
+}
+
+impl fmt::Display for NBTError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NBTError::Io(ref err) => write!(f, "I/O error: {}", err),
+            NBTError::Parse { offset, tag: Some(tag) } => {
+                write!(f, "Parse failed at byte {} while reading {}", offset, tag_id_name(tag).unwrap_or("unknown"))
+            }
+            NBTError::Parse { offset, tag: None } => write!(f, "Parse failed at byte {}", offset),
+            NBTError::TrailingBytes(len) => write!(f, "Found {} bytes after the root tag", len),
+            NBTError::DuplicateKey(ref key) => write!(f, "Compound contains the key {:?} more than once", key),
+            NBTError::InvalidRoot { found } => {
+                write!(f, "Type of root tag is required to be TagCompound but found {} ({})", tag_id_name(found).unwrap_or("unknown"), found)
+            }
+            NBTError::UnexpectedType { expected, found } => {
+                write!(f, "Expected tag of type {} ({}) but found {} ({})",
+                       tag_id_name(expected).unwrap_or("unknown"), expected,
+                       tag_id_name(found).unwrap_or("unknown"), found)
+            }
+            NBTError::TooLong { kind, len, max } => {
+                write!(f, "Length {} of {} exceeds the maximum of {}", len, kind, max)
+            }
+            NBTError::NegativeLength { kind, len } => write!(f, "Length {} of {} is negative", len, kind),
+            NBTError::UnexpectedEof { kind, read, expected } => {
+                write!(f, "Input ends after {} of the {} elements of {}", read, expected, kind)
+            }
+            NBTError::BudgetExceeded { total, max } => {
+                write!(f, "Reading requires allocating at least {} bytes, exceeding the budget of {}", total, max)
+            }
+            NBTError::InvalidValue(ref msg) => write!(f, "{}", msg),
+            NBTError::InvalidSnbt { offset, msg } => write!(f, "Invalid SNBT at position {}: {}", offset, msg),
+            NBTError::UnknownCompression(id) => write!(f, "Unknown compression type {}", id),
+            NBTError::InvalidRegion(ref msg) => write!(f, "Invalid region file: {}", msg),
+        }
+    }
+}
+
+impl Error for NBTError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NBTError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NBTError {
+    fn from(err: io::Error) -> NBTError {
+        NBTError::Io(err)
+    }
+}
@@ -1,108 +1,458 @@
-use NBTTag;
+use compression::{self, Compression};
+use error::NBTError;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+#[cfg(all(test, feature = "io"))]
+use Endianness;
 use read;
-use std::error::Error;
+use read::ReadOptions;
+use NBTTag;
+use NbtStats;
+#[cfg(feature = "io")]
 use std::fs::File;
+#[cfg(feature = "io")]
 use std::io::prelude::*;
+#[cfg(feature = "io")]
 use std::path::Path;
 use write;
+use write::WriteOptions;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct NBTFile {
     pub root_name: String,
     pub root: NBTTag,
+    // The compression the file was read with, which is used again when writing it
+    pub compression: Compression,
 }
 
 impl NBTFile {
     pub fn new(root_name: String, root: Option<NBTTag>) -> NBTFile {
-        let mut file: NBTFile;
-
-        file.root_name = root_name;
-
-        if let Some(root_val) = root {
-            file.root = root_val;
+        NBTFile {
+            root_name,
+            root: root.unwrap_or_else(|| NBTTag::TagCompound(IndexMap::new())),
+            compression: Compression::None,
         }
-
-        file
     }
 
     pub fn get_root(&self) -> &NBTTag {
-        return &self.root;
+        &self.root
     }
 
     pub fn get_root_mut(&mut self) -> &mut NBTTag {
-        return &mut self.root;
+        &mut self.root
+    }
+
+    // The root of a file is always a TagCompound, unless the file was constructed with another root
+    // tag by hand or read with ReadOptions::allow_non_compound_root, in which case this panics
+    pub fn root_compound(&self) -> &IndexMap<String, NBTTag> {
+        match self.root {
+            NBTTag::TagCompound(ref map) => map,
+            _ => panic!("Type of root tag is required to be TagCompound"),
+        }
     }
 
-    pub fn set_root(&mut self, new_root: NBTTag) -> Result<(), String> {
+    pub fn root_compound_mut(&mut self) -> &mut IndexMap<String, NBTTag> {
+        match self.root {
+            NBTTag::TagCompound(ref mut map) => map,
+            _ => panic!("Type of root tag is required to be TagCompound"),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&NBTTag> {
+        self.root.get(key)
+    }
+
+    pub fn set_root(&mut self, new_root: NBTTag) -> Result<(), NBTError> {
         if let NBTTag::TagCompound(_) = new_root {
             self.root = new_root;
             return Ok(())
         }
 
-        Err("Type of root tag is required to be TagCompound".to_owned())
+        Err(NBTError::InvalidRoot { found: write::get_tag_id(&new_root) })
     }
 
     pub fn set_root_name(&mut self, new_root_name: String) {
         self.root_name = new_root_name
     }
 
-    pub fn from_path(path: &str) -> Result<NBTFile, String> {
-        let path = Path::new(path);
-        let display = path.display();
-
-        let mut file = match File::open(&path) {
-            Err(msg) => return Err(format!("File {} could not be opened: {}", display, msg.description())),
-            Ok(file) => file,
-        };
+    #[cfg(feature = "io")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<NBTFile, NBTError> {
+        let mut file = File::open(path)?;
 
         NBTFile::from_file(&mut file)
     }
 
-    pub fn from_file(file: &mut File) -> Result<NBTFile, String> {
+    #[cfg(feature = "io")]
+    pub fn from_file(file: &mut File) -> Result<NBTFile, NBTError> {
         let mut bytes: Vec<u8> = Vec::new();
 
-        match file.read_to_end(&mut bytes) {
-            Err(msg) => return Err(format!("Error reading file: {}", msg.description())),
-            Ok(_) => (),
-        };
+        file.read_to_end(&mut bytes)?;
 
         NBTFile::from_bytes(&bytes)
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> Result<NBTFile, String> {
-        let file_raw = read::read_nbt_file(bytes.as_slice());
+    // Reads a file that is either gzip or zlib compressed, or not compressed at all
+    pub fn from_bytes(bytes: &[u8]) -> Result<NBTFile, NBTError> {
+        NBTFile::read(bytes, &ReadOptions::default())
+    }
 
-        if let Ok(file) = file_raw {
-            if let Some(file_root) = file.1 {
-                return Ok(file_root);
-            } else {
-                return Err("File could not be read".to_owned());
-            }
-        }
+    // Reads a file as configured by options. Compressed files can only be read with the std
+    // feature.
+    pub fn read(bytes: &[u8], options: &ReadOptions) -> Result<NBTFile, NBTError> {
+        NBTFileRef::read(bytes, options).map(NBTFileRef::into_owned)
+    }
+
+    // Reads the nameless, uncompressed form of files sent over the network since Minecraft 1.20.2,
+    // see to_network_bytes. The root name of the file is empty.
+    pub fn from_network_bytes(bytes: &[u8]) -> Result<NBTFile, NBTError> {
+        let root = read::read_headless_nbt(bytes)?;
+        let mut file = NBTFile::new(String::new(), None);
+        file.set_root(root)?;
 
-        Err("File could not be read".to_owned())
+        Ok(file)
     }
 
-    pub fn write_to_path(&self, path: &str) -> Result<(), String> {
-        let path = Path::new(path);
-        let display = path.display();
+    // Sets the compression that is used when writing the file
+    pub fn with_compression(mut self, compression: Compression) -> NBTFile {
+        self.compression = compression;
+        self
+    }
 
-        let mut file = match File::open(&path) {
-            Err(msg) => return Err(format!("File {} could not be opened: {}", display, msg.description())),
-            Ok(file) => file,
-        };
+    #[cfg(feature = "io")]
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), NBTError> {
+        let mut file = File::create(path)?;
 
         self.write_to_file(&mut file)
     }
 
-    pub fn write_to_file(&self, file: &mut File) -> Result<(), String> {
-        match file.write_all(self.as_bytes()?.as_slice()) {
-            Err(msg) => return Err(format!("Error writing to file: {}", msg.description())),
-            Ok(_) => return Ok(()),
+    #[cfg(feature = "io")]
+    pub fn write_to_file(&self, file: &mut File) -> Result<(), NBTError> {
+        file.write_all(self.as_bytes()?.as_slice())?;
+
+        Ok(())
+    }
+
+    // Serializes the file, compressed with the file's compression
+    pub fn as_bytes(&self) -> Result<Vec<u8>, NBTError> {
+        self.to_bytes(&WriteOptions::default())
+    }
+
+    // Serializes the file as configured by options
+    pub fn to_bytes(&self, options: &WriteOptions) -> Result<Vec<u8>, NBTError> {
+        let root_name = options.root_name.as_deref().unwrap_or(&self.root_name);
+        let bytes = write::write_tag_with_options(&self.root, true, true, Some(root_name), options)?;
+
+        compress(&bytes, options.compression.unwrap_or(self.compression))
+    }
+
+    // Serializes the root compound without its name and without compression, as sent over the
+    // network since Minecraft 1.20.2
+    pub fn to_network_bytes(&self) -> Result<Vec<u8>, NBTError> {
+        write::write_tag(&self.root, true, false, None)
+    }
+
+    // The length of the file when written with the given compression
+    #[cfg(feature = "io")]
+    pub fn compressed_len(&self, compression: Compression) -> Result<usize, NBTError> {
+        compression::compressed_len(&write::write_tag(&self.root, true, true, Some(&*self.root_name))?, compression)
+    }
+
+    // Counts the tags of the whole file, see NBTTag::stats
+    pub fn stats(&self) -> NbtStats {
+        self.root.stats()
+    }
+
+    // The length of the file when written without compression
+    pub fn serialized_len(&self) -> usize {
+        write::serialized_len(&self.root, true, true, Some(&*self.root_name))
+    }
+}
+
+// Builds an NBTFile by adding entries to its root compound one at a time
+#[derive(Debug, Clone, Default)]
+pub struct NBTFileBuilder {
+    root_name: String,
+    root: IndexMap<String, NBTTag>,
+}
+
+impl NBTFileBuilder {
+    pub fn new() -> NBTFileBuilder {
+        NBTFileBuilder::default()
+    }
+
+    pub fn name(mut self, root_name: &str) -> NBTFileBuilder {
+        self.root_name = root_name.to_owned();
+        self
+    }
+
+    pub fn put<K: Into<String>, V: Into<NBTTag>>(mut self, key: K, value: V) -> NBTFileBuilder {
+        self.root.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn put_list<K, I>(mut self, key: K, elems: I) -> NBTFileBuilder
+        where K: Into<String>, I: IntoIterator, I::Item: Into<NBTTag> {
+        self.root.insert(key.into(), NBTTag::TagList(elems.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    pub fn build(self) -> NBTFile {
+        NBTFile::new(self.root_name, Some(NBTTag::TagCompound(self.root)))
+    }
+}
+
+impl<'a> TryFrom<&'a NBTFile> for Vec<u8> {
+    type Error = NBTError;
+
+    fn try_from(file: &'a NBTFile) -> Result<Vec<u8>, NBTError> {
+        file.as_bytes()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for NBTFile {
+    type Error = NBTError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<NBTFile, NBTError> {
+        NBTFile::from_bytes(bytes)
+    }
+}
+
+// An NBTFile whose root name may still borrow from the bytes it was read from, for when the name
+// is only inspected and the allocation of an owned copy is not worth it
+#[derive(Debug, PartialEq, Clone)]
+pub struct NBTFileRef<'a> {
+    pub root_name: Cow<'a, str>,
+    pub root: NBTTag,
+    pub compression: Compression,
+}
+
+impl<'a> NBTFileRef<'a> {
+    // Reads a file like NBTFile::read. The name of compressed files can not borrow from bytes, so
+    // it is owned in that case.
+    pub fn read(bytes: &'a [u8], options: &ReadOptions) -> Result<NBTFileRef<'a>, NBTError> {
+        let compression = options.compression.unwrap_or_else(|| compression::detect(bytes));
+
+        if compression != Compression::None {
+            let decompressed = decompress(bytes, compression)?;
+            let file = NBTFileRef::parse(&decompressed, options)?;
+            return Ok(NBTFileRef {
+                root_name: Cow::Owned(file.root_name.into_owned()),
+                root: file.root,
+                compression,
+            });
+        }
+
+        NBTFileRef::parse(bytes, options)
+    }
+
+    fn parse<'b>(bytes: &'b [u8], options: &ReadOptions) -> Result<NBTFileRef<'b>, NBTError> {
+        // Checked up front so that a blob that is not a file is not read to the end first
+        match bytes.first() {
+            Some(&found) if found != 10 && !options.allow_non_compound_root => return Err(NBTError::InvalidRoot { found }),
+            _ => (),
+        }
+
+        match read::read_nbt_file(bytes, options) {
+            Ok((rest, Some(_))) if options.reject_trailing && !rest.is_empty() => Err(NBTError::TrailingBytes(rest.len())),
+            Ok((_, Some(file))) => Ok(file),
+            Ok((_, None)) => Err(NBTError::InvalidRoot { found: bytes[0] }),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn into_owned(self) -> NBTFile {
+        NBTFile {
+            root_name: self.root_name.into_owned(),
+            root: self.root,
+            compression: self.compression,
         }
     }
+}
+
+#[cfg(feature = "io")]
+fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, NBTError> {
+    compression::decompress(bytes, compression)
+}
+
+#[cfg(feature = "io")]
+fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, NBTError> {
+    compression::compress(bytes, compression)
+}
+
+#[cfg(not(feature = "io"))]
+fn decompress(_bytes: &[u8], _compression: Compression) -> Result<Vec<u8>, NBTError> {
+    Err(NBTError::InvalidValue("Compression requires the io feature".to_owned()))
+}
 
-    pub fn as_bytes(&self) -> Result<Vec<u8>, String> {
-        return write::write_tag(&self.root, true, true, Some(&self.root_name));
+#[cfg(not(feature = "io"))]
+fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>, NBTError> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        _ => Err(NBTError::InvalidValue("Compression requires the io feature".to_owned())),
     }
 }
+
+#[test]
+fn test_root_compound() {
+    let mut file = NBTFile::new("e".to_owned(), None);
+    file.root_compound_mut().insert("Hello".to_owned(), NBTTag::TagInt(1));
+
+    assert_eq!(file.root_compound().len(), 1);
+    assert_eq!(file.get("Hello"), Some(&NBTTag::TagInt(1)));
+    assert_eq!(file.get("Bye"), None);
+}
+
+#[test]
+fn test_from_bytes_slice() {
+    let file = NBTFileBuilder::new().name("e").put("Hello", 1).build();
+
+    let bytes = file.as_bytes().unwrap();
+
+    let mut buffer = vec![0xFF; 4];
+    buffer.extend(&bytes);
+
+    assert_eq!(NBTFile::from_bytes(&buffer[4..]).unwrap(), file);
+    assert_eq!(NBTFile::from_bytes(&bytes).unwrap(), file);
+}
+
+#[cfg(feature = "io")]
+#[test]
+fn test_from_path_detects_gzip() {
+    let path = ::std::env::temp_dir().join(format!("rust_nbt_gzip_{}.dat", ::std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let file = NBTFileBuilder::new().name("Data").put("SpawnY", 64).build();
+    let bytes = compression::compress(&file.as_bytes().unwrap(), Compression::Gzip).unwrap();
+    File::create(path).unwrap().write_all(&bytes).unwrap();
+
+    let mut read = NBTFile::from_path(path).unwrap();
+    assert_eq!(read.compression, Compression::Gzip);
+    assert_eq!(read.get("SpawnY"), Some(&NBTTag::TagInt(64)));
+
+    read.root_compound_mut().insert("SpawnX".to_owned(), NBTTag::TagInt(8));
+    read.write_to_path(path).unwrap();
+
+    let mut written = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut written).unwrap();
+    ::std::fs::remove_file(path).unwrap();
+
+    assert_eq!(compression::detect(&written), Compression::Gzip);
+    assert_eq!(NBTFile::from_bytes(&written).unwrap(), read);
+}
+
+#[cfg(feature = "io")]
+#[test]
+fn test_compression_round_trip() {
+    let file = NBTFileBuilder::new().name("Data").put("SpawnY", 64).build();
+
+    for compression in &[Compression::None, Compression::Gzip, Compression::Zlib] {
+        let bytes = file.clone().with_compression(*compression).as_bytes().unwrap();
+
+        assert_eq!(file.compressed_len(*compression).unwrap(), bytes.len());
+        assert_eq!(compression::detect(&bytes), *compression);
+        assert_eq!(NBTFile::from_bytes(&bytes).unwrap().compression, *compression);
+    }
+}
+
+#[test]
+fn test_file_ref_borrows_name() {
+    let file = NBTFileBuilder::new().name("Data").put("SpawnY", 64).build();
+    let bytes = file.as_bytes().unwrap();
+
+    let file_ref = NBTFileRef::read(&bytes, &ReadOptions::default()).unwrap();
+    assert!(matches!(file_ref.root_name, Cow::Borrowed("Data")));
+    assert_eq!(file_ref.into_owned(), file);
+}
+
+#[test]
+fn test_network_bytes() {
+    let file = NBTFileBuilder::new().name("Data").put("a", 1i8).build().with_compression(Compression::Gzip);
+
+    let bytes = file.to_network_bytes().unwrap();
+    assert_eq!(bytes, vec![0x0A, 0x01, 0x00, 0x01, 0x61, 0x01, 0x00]);
+
+    let read = NBTFile::from_network_bytes(&bytes).unwrap();
+    assert_eq!(read.root_name, "");
+    assert_eq!(read.root, file.root);
+    assert!(NBTFile::from_network_bytes(&[0x08, 0x00, 0x00]).is_err());
+}
+
+#[test]
+fn test_read_checks_root_type() {
+    let bytes = [0x08, 0x00, 0x00, 0x00, 0x01, 0x61];
+
+    assert_eq!(NBTFile::from_bytes(&bytes).unwrap_err().to_string(),
+               "Type of root tag is required to be TagCompound but found TAG_String (8)");
+    assert_eq!(NBTFile::from_bytes(&bytes[..1]).unwrap_err().to_string(),
+               "Type of root tag is required to be TagCompound but found TAG_String (8)");
+
+    let options = ReadOptions { allow_non_compound_root: true, ..ReadOptions::default() };
+    assert_eq!(NBTFile::read(&bytes, &options).unwrap().root, NBTTag::TagString("a".to_owned()));
+}
+
+#[test]
+fn test_try_from() {
+    use std::convert::TryInto;
+
+    let file = NBTFileBuilder::new().name("e").put("Hello", 1).build();
+
+    let bytes: Vec<u8> = (&file).try_into().unwrap();
+    assert_eq!(bytes, file.as_bytes().unwrap());
+    assert_eq!(NBTFile::try_from(&bytes[..]).unwrap(), file);
+    assert!(NBTFile::try_from(&bytes[..3]).is_err());
+}
+
+#[test]
+fn test_reject_trailing() {
+    let mut bytes = NBTFileBuilder::new().put("a", 1).build().as_bytes().unwrap();
+    bytes.extend_from_slice(&[0x00, 0x01]);
+
+    assert!(NBTFile::from_bytes(&bytes).is_ok());
+
+    let options = ReadOptions { reject_trailing: true, ..ReadOptions::default() };
+    assert_eq!(NBTFile::read(&bytes, &options).unwrap_err().to_string(), "Found 2 bytes after the root tag");
+    assert!(NBTFile::read(&bytes[..bytes.len() - 2], &options).is_ok());
+}
+
+#[cfg(feature = "io")]
+#[test]
+fn test_read_and_write_options() {
+    let file = NBTFileBuilder::new().name("Data").put("SpawnY", 64).build();
+
+    let options = WriteOptions {
+        endianness: Endianness::Little,
+        compression: Some(Compression::Zlib),
+        root_name: Some("Level".to_owned()),
+        sort_keys: false,
+        string_encoding: read::StringEncoding::Mutf8,
+    };
+    let bytes = file.to_bytes(&options).unwrap();
+
+    let read_options = ReadOptions { endianness: Endianness::Little, ..ReadOptions::default() };
+    let read = NBTFile::read(&bytes, &read_options).unwrap();
+
+    assert_eq!(read.root_name, "Level");
+    assert_eq!(read.compression, Compression::Zlib);
+    assert_eq!(read.root, file.root);
+
+    let raw = ReadOptions { compression: Some(Compression::None), ..read_options };
+    assert!(NBTFile::read(&bytes, &raw).is_err());
+}
+
+#[test]
+fn test_builder() {
+    let file = NBTFileBuilder::new()
+        .name("Data")
+        .put("LevelName", "World")
+        .put("SpawnY", 64)
+        .put_list("Pos", vec![1.5f64, 64.0, -3.5])
+        .build();
+
+    assert_eq!(file.root_name, "Data");
+    assert_eq!(file.get("LevelName"), Some(&NBTTag::TagString("World".to_owned())));
+    assert_eq!(file.get("SpawnY"), Some(&NBTTag::TagInt(64)));
+    assert_eq!(file.get("Pos"), Some(&NBTTag::TagList(vec![NBTTag::TagDouble(1.5),
+                                                          NBTTag::TagDouble(64.0),
+                                                          NBTTag::TagDouble(-3.5)])));
+}
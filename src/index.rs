@@ -0,0 +1,84 @@
+use NBTTag;
+
+// Types that can address an element of a tag: a usize indexes into a TagList and a string
+// looks up a key in a TagCompound. Used by NBTTag::get, get_mut and remove.
+pub trait Index {
+    fn index_into<'a>(&self, tag: &'a NBTTag) -> Option<&'a NBTTag>;
+    fn index_into_mut<'a>(&self, tag: &'a mut NBTTag) -> Option<&'a mut NBTTag>;
+    fn remove_from(&self, tag: &mut NBTTag) -> Option<NBTTag>;
+}
+
+impl Index for usize {
+    fn index_into<'a>(&self, tag: &'a NBTTag) -> Option<&'a NBTTag> {
+        match *tag {
+            NBTTag::TagList(ref list) => list.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'a>(&self, tag: &'a mut NBTTag) -> Option<&'a mut NBTTag> {
+        match *tag {
+            NBTTag::TagList(ref mut list) => list.get_mut(*self),
+            _ => None,
+        }
+    }
+
+    fn remove_from(&self, tag: &mut NBTTag) -> Option<NBTTag> {
+        match *tag {
+            NBTTag::TagList(ref mut list) if *self < list.len() => Some(list.remove(*self)),
+            _ => None,
+        }
+    }
+}
+
+impl Index for str {
+    fn index_into<'a>(&self, tag: &'a NBTTag) -> Option<&'a NBTTag> {
+        match *tag {
+            NBTTag::TagCompound(ref map) => map.get(self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'a>(&self, tag: &'a mut NBTTag) -> Option<&'a mut NBTTag> {
+        match *tag {
+            NBTTag::TagCompound(ref mut map) => map.get_mut(self),
+            _ => None,
+        }
+    }
+
+    // Keeps the order of the remaining entries
+    fn remove_from(&self, tag: &mut NBTTag) -> Option<NBTTag> {
+        match *tag {
+            NBTTag::TagCompound(ref mut map) => map.shift_remove(self),
+            _ => None,
+        }
+    }
+}
+
+impl Index for String {
+    fn index_into<'a>(&self, tag: &'a NBTTag) -> Option<&'a NBTTag> {
+        self.as_str().index_into(tag)
+    }
+
+    fn index_into_mut<'a>(&self, tag: &'a mut NBTTag) -> Option<&'a mut NBTTag> {
+        self.as_str().index_into_mut(tag)
+    }
+
+    fn remove_from(&self, tag: &mut NBTTag) -> Option<NBTTag> {
+        self.as_str().remove_from(tag)
+    }
+}
+
+impl<T: Index + ?Sized> Index for &T {
+    fn index_into<'a>(&self, tag: &'a NBTTag) -> Option<&'a NBTTag> {
+        (**self).index_into(tag)
+    }
+
+    fn index_into_mut<'a>(&self, tag: &'a mut NBTTag) -> Option<&'a mut NBTTag> {
+        (**self).index_into_mut(tag)
+    }
+
+    fn remove_from(&self, tag: &mut NBTTag) -> Option<NBTTag> {
+        (**self).remove_from(tag)
+    }
+}
@@ -0,0 +1,331 @@
+use error::NBTError;
+use indexmap::IndexMap;
+use path;
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem;
+use write;
+use NBTTag;
+
+// Converts a tag into JSON. In lossy mode every tag maps to the closest JSON type, so the tag
+// types can't be recovered. In lossless mode every tag becomes an object of the form
+// {"type": "long", "value": ...}, where non-finite floats are written as the strings "NaN",
+// "Infinity" and "-Infinity".
+pub fn to_json_value(tag: &NBTTag, lossless: bool) -> Value {
+    if lossless {
+        let mut object = Map::new();
+        object.insert("type".to_owned(), Value::String(json_type_name(tag).to_owned()));
+        object.insert("value".to_owned(), lossless_value(tag));
+        return Value::Object(object);
+    }
+
+    match *tag {
+        NBTTag::TagEnd => Value::Null,
+        NBTTag::TagByte(value) => Value::from(value),
+        NBTTag::TagShort(value) => Value::from(value),
+        NBTTag::TagInt(value) => Value::from(value),
+        NBTTag::TagLong(value) => Value::from(value),
+        NBTTag::TagFloat(value) => Number::from_f64(f64::from(value)).map_or(Value::Null, Value::Number),
+        NBTTag::TagDouble(value) => Number::from_f64(value).map_or(Value::Null, Value::Number),
+        NBTTag::TagByteArray(ref value) => Value::from(value.clone()),
+        NBTTag::TagString(ref value) => Value::String(value.clone()),
+        NBTTag::TagList(ref value) => Value::Array(value.iter().map(|tag| to_json_value(tag, false)).collect()),
+        NBTTag::TagCompound(ref value) => {
+            Value::Object(value.iter().map(|(key, tag)| (key.clone(), to_json_value(tag, false))).collect())
+        }
+        NBTTag::TagIntArray(ref value) => Value::from(value.clone()),
+        NBTTag::TagLongArray(ref value) => Value::from(value.clone()),
+    }
+}
+
+fn lossless_value(tag: &NBTTag) -> Value {
+    match *tag {
+        NBTTag::TagFloat(value) => float_value(f64::from(value)),
+        NBTTag::TagDouble(value) => float_value(value),
+        NBTTag::TagList(ref value) => Value::Array(value.iter().map(|tag| to_json_value(tag, true)).collect()),
+        NBTTag::TagCompound(ref value) => {
+            Value::Object(value.iter().map(|(key, tag)| (key.clone(), to_json_value(tag, true))).collect())
+        }
+        _ => to_json_value(tag, false),
+    }
+}
+
+fn float_value(value: f64) -> Value {
+    match Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => Value::String("NaN".to_owned()),
+        None if value > 0.0 => Value::String("Infinity".to_owned()),
+        None => Value::String("-Infinity".to_owned()),
+    }
+}
+
+fn json_type_name(tag: &NBTTag) -> &'static str {
+    JSON_TYPE_NAMES[write::get_tag_id(tag) as usize]
+}
+
+// Converts JSON back into a tag. Objects of the form {"type": ..., "value": ...} as produced by the
+// lossless mode of to_json_value are decoded exactly. Everything else is inferred:
+//
+// - booleans become TagByte 0 or 1
+// - integers become TagInt, or TagLong if they don't fit in an i32
+// - other numbers become TagDouble
+// - strings become TagString
+// - arrays become a TagList, whose elements are required to infer to the same type
+// - objects become a TagCompound, keeping the order of serde_json's map
+// - null can't be converted
+//
+// hints maps paths (in the dotted notation, e.g. `Pos[0]`) to the tag id the value at that path
+// should be converted to, e.g. 11 to read an array of numbers as a TagIntArray.
+pub fn from_json_value(value: &Value, hints: &HashMap<String, u8>) -> Result<NBTTag, NBTError> {
+    from_json_at(value, "", hints)
+}
+
+fn from_json_at(value: &Value, current: &str, hints: &HashMap<String, u8>) -> Result<NBTTag, NBTError> {
+    if let Some(&tag_id) = hints.get(current) {
+        return from_json_hinted(value, tag_id, current, hints);
+    }
+
+    if let Some((tag_id, inner)) = as_type_tagged(value) {
+        return from_json_lossless(inner, tag_id, current);
+    }
+
+    match *value {
+        Value::Null => Err(NBTError::InvalidValue(format!("Value at '{}' is null, which has no NBT equivalent", current))),
+        Value::Bool(value) => Ok(NBTTag::TagByte(value as i8)),
+        Value::Number(ref number) => {
+            if let Some(value) = number.as_i64() {
+                if value >= i64::from(i32::MIN) && value <= i64::from(i32::MAX) {
+                    return Ok(NBTTag::TagInt(value as i32));
+                }
+
+                return Ok(NBTTag::TagLong(value));
+            }
+
+            if number.is_u64() {
+                return Err(NBTError::InvalidValue(format!("Number at '{}' does not fit in a TagLong", current)));
+            }
+
+            Ok(NBTTag::TagDouble(number.as_f64().unwrap_or(0.0)))
+        }
+        Value::String(ref value) => Ok(NBTTag::TagString(value.clone())),
+        Value::Array(ref values) => {
+            let mut elems: Vec<NBTTag> = Vec::new();
+
+            for (index, value) in values.iter().enumerate() {
+                let child = path::child_index(current, index);
+                let elem = from_json_at(value, &child, hints)?;
+
+                if let Some(first) = elems.first() {
+                    if mem::discriminant(first) != mem::discriminant(&elem) {
+                        return Err(NBTError::InvalidValue(format!("Element at '{}' differs in type from the rest of the list", child)));
+                    }
+                }
+
+                elems.push(elem);
+            }
+
+            Ok(NBTTag::TagList(elems))
+        }
+        Value::Object(ref map) => {
+            let mut compound = IndexMap::new();
+
+            for (key, value) in map {
+                compound.insert(key.clone(), from_json_at(value, &path::child_key(current, key), hints)?);
+            }
+
+            Ok(NBTTag::TagCompound(compound))
+        }
+    }
+}
+
+fn from_json_hinted(value: &Value, tag_id: u8, current: &str, hints: &HashMap<String, u8>) -> Result<NBTTag, NBTError> {
+    let invalid = || NBTError::InvalidValue(format!("Value at '{}' can't be converted to tag id {}", current, tag_id));
+
+    match tag_id {
+        1..=4 => {
+            let value = value.as_i64().or_else(|| value.as_bool().map(i64::from)).ok_or_else(invalid)?;
+
+            match tag_id {
+                1 => i8::try_from(value).map(NBTTag::TagByte).map_err(|_| invalid()),
+                2 => i16::try_from(value).map(NBTTag::TagShort).map_err(|_| invalid()),
+                3 => i32::try_from(value).map(NBTTag::TagInt).map_err(|_| invalid()),
+                _ => Ok(NBTTag::TagLong(value)),
+            }
+        }
+        5 => value.as_f64().map(|value| NBTTag::TagFloat(value as f32)).ok_or_else(invalid),
+        6 => value.as_f64().map(NBTTag::TagDouble).ok_or_else(invalid),
+        7 | 11 | 12 => {
+            let values = value.as_array().ok_or_else(invalid)?;
+            let mut ints = Vec::new();
+
+            for value in values {
+                ints.push(value.as_i64().ok_or_else(invalid)?);
+            }
+
+            match tag_id {
+                7 => ints.iter().map(|&value| i8::try_from(value)).collect::<Result<_, _>>()
+                    .map(NBTTag::TagByteArray).map_err(|_| invalid()),
+                11 => ints.iter().map(|&value| i32::try_from(value)).collect::<Result<_, _>>()
+                    .map(NBTTag::TagIntArray).map_err(|_| invalid()),
+                _ => Ok(NBTTag::TagLongArray(ints)),
+            }
+        }
+        8 => value.as_str().map(|value| NBTTag::TagString(value.to_owned())).ok_or_else(invalid),
+        9 | 10 => {
+            let tag = from_json_at(value, current, &without_hint(hints, current))?;
+
+            match (tag_id, &tag) {
+                (9, &NBTTag::TagList(_)) | (10, &NBTTag::TagCompound(_)) => Ok(tag),
+                _ => Err(invalid()),
+            }
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn without_hint(hints: &HashMap<String, u8>, current: &str) -> HashMap<String, u8> {
+    let mut hints = hints.clone();
+    hints.remove(current);
+    hints
+}
+
+fn from_json_lossless(value: &Value, tag_id: u8, current: &str) -> Result<NBTTag, NBTError> {
+    let invalid = || NBTError::InvalidValue(format!("Value at '{}' is not a valid {}", current, JSON_TYPE_NAMES[tag_id as usize]));
+
+    match tag_id {
+        0 => Ok(NBTTag::TagEnd),
+        5 | 6 => {
+            let float = match *value {
+                Value::String(ref name) if name == "NaN" => f64::NAN,
+                Value::String(ref name) if name == "Infinity" => f64::INFINITY,
+                Value::String(ref name) if name == "-Infinity" => f64::NEG_INFINITY,
+                _ => value.as_f64().ok_or_else(invalid)?,
+            };
+
+            if tag_id == 5 {
+                Ok(NBTTag::TagFloat(float as f32))
+            } else {
+                Ok(NBTTag::TagDouble(float))
+            }
+        }
+        9 => {
+            let values = value.as_array().ok_or_else(invalid)?;
+            let mut elems = Vec::new();
+
+            for (index, value) in values.iter().enumerate() {
+                let child = path::child_index(current, index);
+                let (tag_id, inner) = as_type_tagged(value).ok_or_else(|| NBTError::InvalidValue(format!("Element at '{}' has no type", child)))?;
+                elems.push(from_json_lossless(inner, tag_id, &child)?);
+            }
+
+            Ok(NBTTag::TagList(elems))
+        }
+        10 => {
+            let map = value.as_object().ok_or_else(invalid)?;
+            let mut compound = IndexMap::new();
+
+            for (key, value) in map {
+                let child = path::child_key(current, key);
+                let (tag_id, inner) = as_type_tagged(value).ok_or_else(|| NBTError::InvalidValue(format!("Entry at '{}' has no type", child)))?;
+                compound.insert(key.clone(), from_json_lossless(inner, tag_id, &child)?);
+            }
+
+            Ok(NBTTag::TagCompound(compound))
+        }
+        _ => from_json_hinted(value, tag_id, current, &HashMap::new()),
+    }
+}
+
+// Recognizes the {"type": ..., "value": ...} objects written in lossless mode
+fn as_type_tagged(value: &Value) -> Option<(u8, &Value)> {
+    let map = value.as_object()?;
+
+    if map.len() != 2 {
+        return None;
+    }
+
+    let type_name = map.get("type")?.as_str()?;
+    let tag_id = JSON_TYPE_NAMES.iter().position(|name| *name == type_name)?;
+
+    Some((tag_id as u8, map.get("value")?))
+}
+
+// Names used for the "type" field in lossless mode, indexed by tag id
+const JSON_TYPE_NAMES: [&str; 13] = [
+    "end", "byte", "short", "int", "long", "float", "double", "byte_array", "string", "list", "compound", "int_array",
+    "long_array",
+];
+
+#[cfg(test)]
+fn mixed_compound() -> NBTTag {
+    let mut map = IndexMap::new();
+    map.insert("byte".to_owned(), NBTTag::TagByte(1));
+    map.insert("long".to_owned(), NBTTag::TagLong(1 << 40));
+    map.insert("double".to_owned(), NBTTag::TagDouble(0.5));
+    map.insert("name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    map.insert("bytes".to_owned(), NBTTag::TagByteArray(vec![1, 2]));
+    map.insert("list".to_owned(), NBTTag::TagList(vec![NBTTag::TagInt(3)]));
+    NBTTag::TagCompound(map)
+}
+
+#[test]
+fn test_to_json_value_lossy() {
+    let expected = json!({
+        "byte": 1,
+        "long": 1u64 << 40,
+        "double": 0.5,
+        "name": "Steve",
+        "bytes": [1, 2],
+        "list": [3],
+    });
+
+    assert_eq!(to_json_value(&mixed_compound(), false), expected);
+}
+
+#[test]
+fn test_to_json_value_lossless() {
+    let expected = json!({
+        "type": "compound",
+        "value": {
+            "byte": {"type": "byte", "value": 1},
+            "long": {"type": "long", "value": 1u64 << 40},
+            "double": {"type": "double", "value": 0.5},
+            "name": {"type": "string", "value": "Steve"},
+            "bytes": {"type": "byte_array", "value": [1, 2]},
+            "list": {"type": "list", "value": [{"type": "int", "value": 3}]},
+        }
+    });
+
+    assert_eq!(to_json_value(&mixed_compound(), true), expected);
+    assert_eq!(to_json_value(&NBTTag::TagFloat(f32::NAN), true), json!({"type": "float", "value": "NaN"}));
+}
+
+#[test]
+fn test_from_json_value_round_trips_lossless() {
+    let tag = mixed_compound();
+
+    assert_eq!(from_json_value(&to_json_value(&tag, true), &HashMap::new()).unwrap(), tag);
+}
+
+#[test]
+fn test_from_json_value_inference_and_hints() {
+    let value = json!({"small": 1, "big": 1u64 << 40, "pos": [1, 2, 3], "ids": [4, 5], "flag": true});
+
+    let mut hints = HashMap::new();
+    hints.insert("ids".to_owned(), 11);
+    hints.insert("pos[1]".to_owned(), 3);
+
+    let tag = from_json_value(&value, &hints).unwrap();
+
+    let mut expected = IndexMap::new();
+    expected.insert("small".to_owned(), NBTTag::TagInt(1));
+    expected.insert("big".to_owned(), NBTTag::TagLong(1 << 40));
+    expected.insert("pos".to_owned(), NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(2), NBTTag::TagInt(3)]));
+    expected.insert("ids".to_owned(), NBTTag::TagIntArray(vec![4, 5]));
+    expected.insert("flag".to_owned(), NBTTag::TagByte(1));
+
+    assert_eq!(tag, NBTTag::TagCompound(expected));
+    assert!(from_json_value(&json!([1, "a"]), &HashMap::new()).is_err());
+    assert!(from_json_value(&json!(null), &HashMap::new()).is_err());
+}
@@ -0,0 +1,85 @@
+// Reading of the chunk format of Alpha, which predates region files. Every chunk is a separate
+// gzip compressed file c.<x>.<z>.dat, with the coordinates in base 36, in the directory
+// <x mod 64>/<z mod 64> of the world, again in base 36.
+
+use compression::Compression;
+use error::NBTError;
+use file::NBTFile;
+use read::ReadOptions;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use NBTTag;
+
+// Reads the chunk file at path, returning its root compound, which holds the chunk as Level
+pub fn load_alpha_chunk<P: AsRef<Path>>(path: P) -> Result<NBTTag, NBTError> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let options = ReadOptions { compression: Some(Compression::Gzip), ..ReadOptions::default() };
+
+    Ok(NBTFile::read(&bytes, &options)?.root)
+}
+
+// The path of the file of the chunk at the given chunk coordinates in the world directory
+pub fn alpha_chunk_path<P: AsRef<Path>>(world: P, x: i32, z: i32) -> PathBuf {
+    world.as_ref()
+        .join(base36(i64::from(x & 63)))
+        .join(base36(i64::from(z & 63)))
+        .join(format!("c.{}.{}.dat", base36(i64::from(x)), base36(i64::from(z))))
+}
+
+// Formats a number like Java's Integer.toString(value, 36) does
+fn base36(value: i64) -> String {
+    const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut digits = Vec::new();
+    let mut rest = value.abs();
+
+    loop {
+        digits.push(DIGITS[(rest % 36) as usize]);
+        rest /= 36;
+
+        if rest == 0 {
+            break;
+        }
+    }
+
+    if value < 0 {
+        digits.push(b'-');
+    }
+
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+#[test]
+fn test_alpha_chunk_path() {
+    let world = Path::new("world");
+
+    assert_eq!(alpha_chunk_path(world, 0, 0), world.join("0").join("0").join("c.0.0.dat"));
+    assert_eq!(alpha_chunk_path(world, 100, -13), world.join("10").join("1f").join("c.2s.-d.dat"));
+    assert_eq!(base36(i64::from(i32::MIN)), "-zik0zk");
+}
+
+#[test]
+fn test_load_alpha_chunk() {
+    use file::NBTFileBuilder;
+    use indexmap::IndexMap;
+
+    let mut level = IndexMap::new();
+    level.insert("xPos".to_owned(), NBTTag::TagInt(100));
+    let chunk = NBTFileBuilder::new().put("Level", level).build().with_compression(Compression::Gzip);
+
+    let world = ::std::env::temp_dir().join(format!("rust_nbt_alpha_{}", ::std::process::id()));
+    let path = alpha_chunk_path(&world, 100, -13);
+    ::std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    chunk.write_to_path(&path).unwrap();
+
+    let read = load_alpha_chunk(&path);
+    let uncompressed = chunk.with_compression(Compression::None).write_to_path(&path).map(|()| load_alpha_chunk(&path));
+    ::std::fs::remove_dir_all(&world).unwrap();
+
+    assert_eq!(read.unwrap().get("Level").and_then(|level| level.get("xPos")), Some(&NBTTag::TagInt(100)));
+    assert!(uncompressed.unwrap().is_err());
+}
@@ -1,15 +1,80 @@
 #[macro_use]
 extern crate nom;
 extern crate byteorder;
+#[cfg(feature = "io")]
+extern crate flate2;
+extern crate indexmap;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+#[cfg(feature = "serde")]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
 
-use std::collections::HashMap;
+pub use convert::{FromNbt, ToNbt};
+pub use diff::{diff, Change};
+pub use error::NBTError;
+pub use index::Index;
+pub use indexmap::IndexMap;
+#[cfg(feature = "serde")]
+pub use json::{from_json_value, to_json_value};
+pub use list::{ListBuilder, ListElement};
+pub use read::{DuplicateKey, ReadOptions, StringEncoding};
+pub use schema::{CompoundSchema, Schema, SchemaError};
+pub use snbt::{from_snbt, to_snbt};
+pub use stats::NbtStats;
+pub use tag_type::TagType;
+pub use tag_ref::NBTTagRef;
+pub use write::WriteOptions;
+use indexmap::map::{Iter, IterMut};
+use path::Segment;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::vec::Vec;
 
+pub mod bitpack;
+pub mod chunk;
+pub mod compression;
+mod convert;
+pub mod debug;
+mod diff;
+mod error;
 pub mod file;
-mod read;
-mod write;
+mod index;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "io")]
+pub mod legacy;
+mod list;
+mod path;
+pub mod read;
+#[cfg(feature = "io")]
+pub mod region;
+#[cfg(feature = "schematic")]
+pub mod schem;
+mod schema;
+mod snbt;
+mod stats;
+mod tag_ref;
+mod tag_type;
+pub mod upgrade;
+pub mod varint;
+pub mod visit;
+pub mod write;
+
+// The byte order of numbers and lengths. Java Edition uses Big, while Bedrock Edition stores its
+// files in Little.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Endianness {
+    Big,
+    Little,
+}
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub enum NBTTag {
     TagEnd,
     TagByte(i8),
@@ -21,7 +86,1087 @@ pub enum NBTTag {
     TagByteArray(Vec<i8>),
     TagString(String),
     TagList(Vec<NBTTag>),
-    TagCompound(HashMap<String, NBTTag>),
+    TagCompound(IndexMap<String, NBTTag>),
     TagIntArray(Vec<i32>),
     TagLongArray(Vec<i64>),
 }
+
+const TAG_NAMES: [&str; 13] = [
+    "TAG_End",
+    "TAG_Byte",
+    "TAG_Short",
+    "TAG_Int",
+    "TAG_Long",
+    "TAG_Float",
+    "TAG_Double",
+    "TAG_Byte_Array",
+    "TAG_String",
+    "TAG_List",
+    "TAG_Compound",
+    "TAG_Int_Array",
+    "TAG_Long_Array",
+];
+
+// Returns the name of the tag type with the given id, e.g. "TAG_String" for 8
+pub fn tag_id_name(id: u8) -> Option<&'static str> {
+    TAG_NAMES.get(id as usize).cloned()
+}
+
+// Combines the halves of a UUID stored as two longs, like UUIDMost and UUIDLeast before 1.16
+pub fn uuid_from_longs(most: i64, least: i64) -> u128 {
+    u128::from(most as u64) << 64 | u128::from(least as u64)
+}
+
+impl NBTTag {
+    pub fn empty_compound() -> NBTTag {
+        NBTTag::TagCompound(IndexMap::new())
+    }
+
+    // A list has no element type of its own, it is that of its first element, so an empty list is
+    // written with the element type of TagEnd, as the game does. See list_element_type.
+    pub fn empty_list() -> NBTTag {
+        NBTTag::TagList(Vec::new())
+    }
+
+    pub fn empty_byte_array() -> NBTTag {
+        NBTTag::TagByteArray(Vec::new())
+    }
+
+    pub fn empty_int_array() -> NBTTag {
+        NBTTag::TagIntArray(Vec::new())
+    }
+
+    pub fn empty_long_array() -> NBTTag {
+        NBTTag::TagLongArray(Vec::new())
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        TAG_NAMES[write::get_tag_id(self) as usize]
+    }
+
+    // Number of bytes the payload of this tag occupies when written, excluding its id and name
+    pub fn serialized_len(&self) -> usize {
+        write::serialized_len(self, false, false, None)
+    }
+
+    // Estimates the number of bytes this tree takes up in memory, including the tag itself and the
+    // capacity of every Vec and String it owns. This is an approximation: compounds are counted as
+    // their entries plus one index per entry, while the actual overhead of the map depends on its
+    // load factor, and allocator overhead is left out entirely.
+    pub fn deep_size_of(&self) -> usize {
+        mem::size_of::<NBTTag>() + self.heap_size()
+    }
+
+    fn heap_size(&self) -> usize {
+        match *self {
+            NBTTag::TagByteArray(ref value) => value.capacity(),
+            NBTTag::TagString(ref value) => value.capacity(),
+            NBTTag::TagList(ref value) => {
+                value.capacity() * mem::size_of::<NBTTag>() + value.iter().map(NBTTag::heap_size).sum::<usize>()
+            }
+            NBTTag::TagCompound(ref map) => {
+                // Every entry holds its hash next to the key and value, and is referred to by an index
+                let entry_size = mem::size_of::<usize>() + mem::size_of::<String>() + mem::size_of::<NBTTag>();
+
+                map.capacity() * (entry_size + mem::size_of::<usize>()) +
+                    map.iter().map(|(key, value)| key.capacity() + value.heap_size()).sum::<usize>()
+            }
+            NBTTag::TagIntArray(ref value) => value.capacity() * mem::size_of::<i32>(),
+            NBTTag::TagLongArray(ref value) => value.capacity() * mem::size_of::<i64>(),
+            _ => 0,
+        }
+    }
+
+    // Iterates the entries of a TagCompound in the order they were inserted or read
+    pub fn compound_iter(&self) -> Option<Iter<'_, String, NBTTag>> {
+        if let NBTTag::TagCompound(ref map) = *self {
+            return Some(map.iter());
+        }
+
+        None
+    }
+
+    pub fn compound_iter_mut(&mut self) -> Option<IterMut<'_, String, NBTTag>> {
+        if let NBTTag::TagCompound(ref mut map) = *self {
+            return Some(map.iter_mut());
+        }
+
+        None
+    }
+
+    // Inserts an entry into a TagCompound, returning the value previously stored under the key.
+    // Returns None without inserting anything if this tag is not a TagCompound.
+    pub fn insert<K: Into<String>>(&mut self, key: K, value: NBTTag) -> Option<NBTTag> {
+        if let NBTTag::TagCompound(ref mut map) = *self {
+            return map.insert(key.into(), value);
+        }
+
+        None
+    }
+
+    // Gets an element of a TagList by index or an entry of a TagCompound by key
+    pub fn get<I: Index>(&self, index: I) -> Option<&NBTTag> {
+        index.index_into(self)
+    }
+
+    pub fn get_mut<I: Index>(&mut self, index: I) -> Option<&mut NBTTag> {
+        index.index_into_mut(self)
+    }
+
+    // Removes an element of a TagList by index or an entry of a TagCompound by key, keeping the
+    // order of the remaining elements
+    pub fn remove<I: Index>(&mut self, index: I) -> Option<NBTTag> {
+        index.remove_from(self)
+    }
+
+    // Looks up a key in a TagCompound ignoring ASCII case, for keys whose capitalization changed
+    // between versions. An exact match is preferred; otherwise this scans all keys, so it is O(n)
+    // unlike get.
+    pub fn get_ci(&self, key: &str) -> Option<&NBTTag> {
+        if let NBTTag::TagCompound(ref map) = *self {
+            return map.get(key).or_else(|| {
+                map.iter().find(|entry| entry.0.eq_ignore_ascii_case(key)).map(|entry| entry.1)
+            });
+        }
+
+        None
+    }
+
+    // Gets an element or entry like get, as an i64 if it is a TagByte, TagShort, TagInt or TagLong,
+    // for fields of which the width differs between versions
+    pub fn get_i64<I: Index>(&self, index: I) -> Option<i64> {
+        match *self.get(index)? {
+            NBTTag::TagByte(value) => Some(i64::from(value)),
+            NBTTag::TagShort(value) => Some(i64::from(value)),
+            NBTTag::TagInt(value) => Some(i64::from(value)),
+            NBTTag::TagLong(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Like get_i64, for a TagFloat or TagDouble
+    pub fn get_f64<I: Index>(&self, index: I) -> Option<f64> {
+        match *self.get(index)? {
+            NBTTag::TagFloat(value) => Some(f64::from(value)),
+            NBTTag::TagDouble(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Appends an element to a TagList, which is required to be of the same type as the elements
+    // already in the list
+    pub fn push(&mut self, value: NBTTag) -> Result<(), NBTError> {
+        match self.list_element_type() {
+            Some(TagType::End) => (),
+            Some(elems_type) if elems_type != value.tag_type() => {
+                return Err(NBTError::UnexpectedType {
+                    expected: elems_type.into(),
+                    found: write::get_tag_id(&value),
+                });
+            }
+            _ => (),
+        }
+
+        if let NBTTag::TagList(ref mut list) = *self {
+            list.push(value);
+            return Ok(());
+        }
+
+        Err(NBTError::UnexpectedType {
+            expected: 9,
+            found: write::get_tag_id(self),
+        })
+    }
+
+    // Consumes a TagList, returning its elements without cloning them
+    pub fn into_list(self) -> Result<Vec<NBTTag>, NBTError> {
+        match self {
+            NBTTag::TagList(list) => Ok(list),
+            other => Err(NBTError::UnexpectedType {
+                expected: 9,
+                found: write::get_tag_id(&other),
+            }),
+        }
+    }
+
+    // Converts a TagByte, TagShort, TagInt or TagLong into the integer tag type tag_type, as long
+    // as the value fits. Values are read strictly by default, this is for readers that opt into
+    // tolerating saves where a field changed integer type between versions, like
+    // FromNbt::from_nbt_lenient.
+    pub fn coerce_int(&self, tag_type: TagType) -> Result<NBTTag, NBTError> {
+        let value = match *self {
+            NBTTag::TagByte(value) => i64::from(value),
+            NBTTag::TagShort(value) => i64::from(value),
+            NBTTag::TagInt(value) => i64::from(value),
+            NBTTag::TagLong(value) => value,
+            _ => return Err(NBTError::UnexpectedType { expected: tag_type.into(), found: write::get_tag_id(self) }),
+        };
+        let overflow = || NBTError::InvalidValue(format!("{} does not fit in a {}", value, tag_type.name()));
+
+        match tag_type {
+            TagType::Byte => i8::try_from(value).map(NBTTag::TagByte).map_err(|_| overflow()),
+            TagType::Short => i16::try_from(value).map(NBTTag::TagShort).map_err(|_| overflow()),
+            TagType::Int => i32::try_from(value).map(NBTTag::TagInt).map_err(|_| overflow()),
+            TagType::Long => Ok(NBTTag::TagLong(value)),
+            _ => Err(NBTError::UnexpectedType { expected: tag_type.into(), found: write::get_tag_id(self) }),
+        }
+    }
+
+    // Reads a UUID stored the way Minecraft does since 1.16: a TagIntArray of exactly four ints,
+    // the most significant first
+    pub fn as_uuid(&self) -> Option<u128> {
+        match self.as_i32_slice()? {
+            words if words.len() == 4 => Some(words.iter().fold(0, |uuid, &word| uuid << 32 | u128::from(word as u32))),
+            _ => None,
+        }
+    }
+
+    pub fn from_uuid(uuid: u128) -> NBTTag {
+        NBTTag::TagIntArray((0..4).rev().map(|word| (uuid >> (32 * word)) as u32 as i32).collect())
+    }
+
+    // Replaces the pair of TagLongs most_key and least_key of a TagCompound, the way UUIDs were
+    // stored before 1.16, with a TagIntArray under new_key in the place of most_key. Returns false
+    // and leaves the tag untouched if it is not a compound or the pair is missing.
+    pub fn upgrade_uuid_fields(&mut self, most_key: &str, least_key: &str, new_key: &str) -> bool {
+        let map = match *self {
+            NBTTag::TagCompound(ref mut map) => map,
+            _ => return false,
+        };
+
+        let (index, most, least) = match (map.get_full(most_key), map.get_full(least_key)) {
+            (Some((index, _, &NBTTag::TagLong(most))), Some((least_index, _, &NBTTag::TagLong(least)))) => {
+                (if least_index < index { index - 1 } else { index }, most, least)
+            }
+            _ => return false,
+        };
+
+        map.shift_remove(most_key);
+        map.shift_remove(least_key);
+        map.shift_insert(index, new_key.to_owned(), NBTTag::from_uuid(uuid_from_longs(most, least)));
+
+        true
+    }
+
+    // Renames the entry old of a compound to new, keeping it at the same position, so that writing
+    // the compound again only changes the name
+    pub fn rename_key(&mut self, old: &str, new: &str) -> Result<(), NBTError> {
+        let map = match *self {
+            NBTTag::TagCompound(ref mut map) => map,
+            ref other => return Err(NBTError::UnexpectedType { expected: 10, found: write::get_tag_id(other) }),
+        };
+
+        let index = map.get_index_of(old).ok_or_else(|| NBTError::InvalidValue(format!("Compound has no key {:?}", old)))?;
+
+        if old == new {
+            return Ok(());
+        }
+
+        if map.contains_key(new) {
+            return Err(NBTError::DuplicateKey(new.to_owned()));
+        }
+
+        let (_, value) = map.shift_remove_index(index).unwrap();
+        map.shift_insert(index, new.to_owned(), value);
+
+        Ok(())
+    }
+
+    // Lists every tag below this one with its path, in the notation of path::segments, in the order
+    // of a depth first walk. Lists and compounds themselves are only included with
+    // include_containers; arrays count as leaves.
+    pub fn flatten(&self, include_containers: bool) -> Vec<(String, &NBTTag)> {
+        let mut output = Vec::new();
+        flatten_at(self, "", include_containers, &mut output);
+        output
+    }
+
+    // Searches the tags below this one depth first, parents before their children, for the first
+    // one pred accepts, returning it with its path. pred is given the key of the tag in its compound,
+    // or for the elements of a list their index, e.g. "0".
+    pub fn find<F: Fn(&str, &NBTTag) -> bool>(&self, pred: F) -> Option<(String, &NBTTag)> {
+        let mut output = Vec::new();
+        find_at(self, "", &pred, true, &mut output);
+        output.pop()
+    }
+
+    // Like find, but returns every tag pred accepts, in the same order
+    pub fn find_all<F: Fn(&str, &NBTTag) -> bool>(&self, pred: F) -> Vec<(String, &NBTTag)> {
+        let mut output = Vec::new();
+        find_at(self, "", &pred, false, &mut output);
+        output
+    }
+
+    // Compares two trees, comparing floats and doubles by their bit pattern, so NaN equals NaN
+    // and 0.0 differs from -0.0. Compounds are equal when they hold the same entries, in any order,
+    // at every depth, while the elements of lists have to be in the same order. This is also what
+    // == does for NBTTag.
+    pub fn bit_eq(&self, other: &NBTTag) -> bool {
+        self.eq_with(other, false)
+    }
+
+    // Like ==, named for when it should be clear that compounds nested anywhere in the trees may
+    // list their entries in a different order
+    pub fn eq_unordered(&self, other: &NBTTag) -> bool {
+        self.eq_with(other, false)
+    }
+
+    // Like ==, but compounds nested anywhere in the trees also have to list their entries in the
+    // same order, as they would be written to a file
+    pub fn eq_ordered(&self, other: &NBTTag) -> bool {
+        self.eq_with(other, true)
+    }
+
+    fn eq_with(&self, other: &NBTTag, ordered: bool) -> bool {
+        match (self, other) {
+            (NBTTag::TagEnd, NBTTag::TagEnd) => true,
+            (NBTTag::TagByte(a), NBTTag::TagByte(b)) => a == b,
+            (NBTTag::TagShort(a), NBTTag::TagShort(b)) => a == b,
+            (NBTTag::TagInt(a), NBTTag::TagInt(b)) => a == b,
+            (NBTTag::TagLong(a), NBTTag::TagLong(b)) => a == b,
+            (NBTTag::TagFloat(a), NBTTag::TagFloat(b)) => a.to_bits() == b.to_bits(),
+            (NBTTag::TagDouble(a), NBTTag::TagDouble(b)) => a.to_bits() == b.to_bits(),
+            (NBTTag::TagByteArray(a), NBTTag::TagByteArray(b)) => a == b,
+            (NBTTag::TagString(a), NBTTag::TagString(b)) => a == b,
+            (NBTTag::TagList(a), NBTTag::TagList(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.eq_with(b, ordered))
+            }
+            (NBTTag::TagCompound(a), NBTTag::TagCompound(b)) if ordered => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|((key_a, a), (key_b, b))| key_a == key_b && a.eq_with(b, true))
+            }
+            (NBTTag::TagCompound(a), NBTTag::TagCompound(b)) => {
+                a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| a.eq_with(b, false)))
+            }
+            (NBTTag::TagIntArray(a), NBTTag::TagIntArray(b)) => a == b,
+            (NBTTag::TagLongArray(a), NBTTag::TagLongArray(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // Calls f with every tag matching glob, a path like `Inventory[0].Count` in which `*` matches
+    // every element of a list or every value of a compound, e.g. `Inventory.*.Count`. Quoted keys
+    // like `"a.b"` and `"*"` are matched literally.
+    pub fn for_each_at<F: FnMut(&mut NBTTag)>(&mut self, glob: &str, mut f: F) {
+        self.for_each_at_segments(&path::segments(glob), &mut f);
+    }
+
+    fn for_each_at_segments<F: FnMut(&mut NBTTag)>(&mut self, segments: &[Segment], f: &mut F) {
+        let (segment, rest) = match segments.split_first() {
+            Some(split) => split,
+            None => return f(self),
+        };
+
+        match (self, segment) {
+            (NBTTag::TagList(list), Segment::Any) => {
+                list.iter_mut().for_each(|elem| elem.for_each_at_segments(rest, f));
+            }
+            (NBTTag::TagCompound(map), Segment::Any) => {
+                map.values_mut().for_each(|value| value.for_each_at_segments(rest, f));
+            }
+            (NBTTag::TagList(list), Segment::Key(key)) => {
+                if let Some(elem) = key.parse().ok().and_then(|index: usize| list.get_mut(index)) {
+                    elem.for_each_at_segments(rest, f);
+                }
+            }
+            (NBTTag::TagCompound(map), Segment::Key(key)) => {
+                if let Some(value) = map.get_mut(key.as_ref()) {
+                    value.for_each_at_segments(rest, f);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Parses the SNBT compound snbt and merges it into this compound, like Minecraft's /data merge
+    pub fn apply_snbt_patch(&mut self, snbt: &str) -> Result<(), NBTError> {
+        self.merge(&from_snbt(snbt)?)
+    }
+
+    // Recursively sorts the entries of every compound by key, so that the serialized form no longer
+    // depends on the order in which the entries were inserted
+    pub fn canonicalize(&mut self) {
+        match *self {
+            NBTTag::TagList(ref mut list) => list.iter_mut().for_each(NBTTag::canonicalize),
+            NBTTag::TagCompound(ref mut map) => {
+                map.sort_keys();
+                map.values_mut().for_each(NBTTag::canonicalize);
+            }
+            _ => (),
+        }
+    }
+
+    // Deep-merges the compound other into this compound. Keys only present in other are added,
+    // nested compounds are merged recursively and any other value in other overwrites ours.
+    // Both tags are required to be a TagCompound, otherwise nothing is changed.
+    pub fn merge(&mut self, other: &NBTTag) -> Result<(), NBTError> {
+        if let (NBTTag::TagCompound(map), NBTTag::TagCompound(other_map)) = (&mut *self, other) {
+            for (key, value) in other_map {
+                if let Some(existing) = map.get_mut(key) {
+                    if let (NBTTag::TagCompound(_), NBTTag::TagCompound(_)) = (&*existing, value) {
+                        existing.merge(value)?;
+                        continue;
+                    }
+                }
+
+                map.insert(key.clone(), value.clone());
+            }
+
+            return Ok(());
+        }
+
+        let found = if let NBTTag::TagCompound(_) = *self { other } else { self };
+
+        Err(NBTError::UnexpectedType {
+            expected: 10,
+            found: write::get_tag_id(found),
+        })
+    }
+}
+
+fn flatten_at<'a>(tag: &'a NBTTag, current: &str, include_containers: bool, output: &mut Vec<(String, &'a NBTTag)>) {
+    let children: Vec<(String, &NBTTag)> = match *tag {
+        NBTTag::TagList(ref list) => list.iter().enumerate().map(|(index, elem)| (path::child_index(current, index), elem)).collect(),
+        NBTTag::TagCompound(ref map) => map.iter().map(|(key, value)| (path::child_key(current, key), value)).collect(),
+        _ => return,
+    };
+
+    for (child, value) in children {
+        match *value {
+            NBTTag::TagList(_) | NBTTag::TagCompound(_) => {
+                if include_containers {
+                    output.push((child.clone(), value));
+                }
+                flatten_at(value, &child, include_containers, output);
+            }
+            _ => output.push((child, value)),
+        }
+    }
+}
+
+fn find_at<'a, F>(tag: &'a NBTTag, current: &str, pred: &F, first_only: bool, output: &mut Vec<(String, &'a NBTTag)>)
+    where F: Fn(&str, &NBTTag) -> bool
+{
+    let children: Vec<(String, String, &NBTTag)> = match *tag {
+        NBTTag::TagList(ref list) => list.iter().enumerate()
+            .map(|(index, elem)| (index.to_string(), path::child_index(current, index), elem))
+            .collect(),
+        NBTTag::TagCompound(ref map) => map.iter()
+            .map(|(key, value)| (key.clone(), path::child_key(current, key), value))
+            .collect(),
+        _ => return,
+    };
+
+    for (key, child, value) in children {
+        if pred(&key, value) {
+            output.push((child.clone(), value));
+
+            if first_only {
+                return;
+            }
+        }
+
+        find_at(value, &child, pred, first_only, output);
+
+        if first_only && !output.is_empty() {
+            return;
+        }
+    }
+}
+
+// Converts between a TagList of numbers and the array of the same numbers, failing when the tag
+// or one of the list's elements is of another type
+macro_rules! array_conversion (($to_array:ident, $to_list:ident, $array:ident, $array_id:expr, $elem:ident, $elem_id:expr) => (
+    impl NBTTag {
+        pub fn $to_array(&self) -> Result<NBTTag, NBTError> {
+            match *self {
+                NBTTag::TagList(ref list) => list.iter()
+                    .map(|elem| match *elem {
+                        NBTTag::$elem(value) => Ok(value),
+                        ref other => Err(NBTError::UnexpectedType { expected: $elem_id, found: write::get_tag_id(other) }),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(NBTTag::$array),
+                ref other => Err(NBTError::UnexpectedType { expected: 9, found: write::get_tag_id(other) }),
+            }
+        }
+
+        pub fn $to_list(&self) -> Result<NBTTag, NBTError> {
+            match *self {
+                NBTTag::$array(ref values) => Ok(NBTTag::TagList(values.iter().map(|&value| NBTTag::$elem(value)).collect())),
+                ref other => Err(NBTError::UnexpectedType { expected: $array_id, found: write::get_tag_id(other) }),
+            }
+        }
+    }
+););
+
+array_conversion!(list_to_byte_array, byte_array_to_list, TagByteArray, 7, TagByte, 1);
+array_conversion!(list_to_int_array, int_array_to_list, TagIntArray, 11, TagInt, 3);
+array_conversion!(list_to_long_array, long_array_to_list, TagLongArray, 12, TagLong, 4);
+
+// Borrows the values of an array tag as a slice, or returns None for any other tag
+macro_rules! array_slice (($as_slice:ident, $as_slice_mut:ident, $array:ident, $elem:ty) => (
+    impl NBTTag {
+        pub fn $as_slice(&self) -> Option<&[$elem]> {
+            match *self {
+                NBTTag::$array(ref values) => Some(values),
+                _ => None,
+            }
+        }
+
+        pub fn $as_slice_mut(&mut self) -> Option<&mut [$elem]> {
+            match *self {
+                NBTTag::$array(ref mut values) => Some(values),
+                _ => None,
+            }
+        }
+    }
+););
+
+array_slice!(as_i8_slice, as_i8_slice_mut, TagByteArray, i8);
+array_slice!(as_i32_slice, as_i32_slice_mut, TagIntArray, i32);
+array_slice!(as_i64_slice, as_i64_slice_mut, TagLongArray, i64);
+
+macro_rules! tag_from (($t:ty, $variant:ident) => (
+    impl From<$t> for NBTTag {
+        fn from(value: $t) -> NBTTag {
+            NBTTag::$variant(value)
+        }
+    }
+););
+
+tag_from!(i8, TagByte);
+tag_from!(i16, TagShort);
+tag_from!(i32, TagInt);
+tag_from!(i64, TagLong);
+tag_from!(f32, TagFloat);
+tag_from!(f64, TagDouble);
+tag_from!(Vec<i8>, TagByteArray);
+tag_from!(String, TagString);
+tag_from!(Vec<NBTTag>, TagList);
+tag_from!(IndexMap<String, NBTTag>, TagCompound);
+tag_from!(Vec<i32>, TagIntArray);
+tag_from!(Vec<i64>, TagLongArray);
+
+impl<'a> From<&'a str> for NBTTag {
+    fn from(value: &'a str) -> NBTTag {
+        NBTTag::TagString(value.to_owned())
+    }
+}
+
+// Equality is bitwise for floats so that it is reflexive and agrees with Hash
+impl PartialEq for NBTTag {
+    fn eq(&self, other: &NBTTag) -> bool {
+        self.bit_eq(other)
+    }
+}
+
+impl Eq for NBTTag {}
+
+// Tags are ordered by their tag id first, so e.g. every TagByte sorts before every TagShort. Tags
+// of the same type are ordered by value: numbers numerically, floats and doubles by the IEEE 754
+// total order of f32::total_cmp (-NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN, which only
+// considers floats equal when their bits are), strings by their bytes and arrays and lists
+// lexicographically by element. Compounds are compared as lists of their entries sorted by key,
+// where entries are ordered by key and then by value. This is consistent with == and Hash.
+impl PartialOrd for NBTTag {
+    fn partial_cmp(&self, other: &NBTTag) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NBTTag {
+    fn cmp(&self, other: &NBTTag) -> Ordering {
+        match (self, other) {
+            (NBTTag::TagEnd, NBTTag::TagEnd) => Ordering::Equal,
+            (NBTTag::TagByte(a), NBTTag::TagByte(b)) => a.cmp(b),
+            (NBTTag::TagShort(a), NBTTag::TagShort(b)) => a.cmp(b),
+            (NBTTag::TagInt(a), NBTTag::TagInt(b)) => a.cmp(b),
+            (NBTTag::TagLong(a), NBTTag::TagLong(b)) => a.cmp(b),
+            (NBTTag::TagFloat(a), NBTTag::TagFloat(b)) => a.total_cmp(b),
+            (NBTTag::TagDouble(a), NBTTag::TagDouble(b)) => a.total_cmp(b),
+            (NBTTag::TagByteArray(a), NBTTag::TagByteArray(b)) => a.cmp(b),
+            (NBTTag::TagString(a), NBTTag::TagString(b)) => a.cmp(b),
+            (NBTTag::TagList(a), NBTTag::TagList(b)) => a.cmp(b),
+            (NBTTag::TagCompound(a), NBTTag::TagCompound(b)) => {
+                let mut a: Vec<(&String, &NBTTag)> = a.iter().collect();
+                let mut b: Vec<(&String, &NBTTag)> = b.iter().collect();
+                a.sort_by(|a, b| a.0.cmp(b.0));
+                b.sort_by(|a, b| a.0.cmp(b.0));
+                a.cmp(&b)
+            }
+            (NBTTag::TagIntArray(a), NBTTag::TagIntArray(b)) => a.cmp(b),
+            (NBTTag::TagLongArray(a), NBTTag::TagLongArray(b)) => a.cmp(b),
+            _ => write::get_tag_id(self).cmp(&write::get_tag_id(other)),
+        }
+    }
+}
+
+// Floats are hashed by their bit pattern and compound entries in sorted key order, so that tags
+// which are equal hash identically regardless of the order their entries were inserted in
+impl Hash for NBTTag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+
+        match *self {
+            NBTTag::TagEnd => (),
+            NBTTag::TagByte(value) => value.hash(state),
+            NBTTag::TagShort(value) => value.hash(state),
+            NBTTag::TagInt(value) => value.hash(state),
+            NBTTag::TagLong(value) => value.hash(state),
+            NBTTag::TagFloat(value) => value.to_bits().hash(state),
+            NBTTag::TagDouble(value) => value.to_bits().hash(state),
+            NBTTag::TagByteArray(ref value) => value.hash(state),
+            NBTTag::TagString(ref value) => value.hash(state),
+            NBTTag::TagList(ref value) => value.hash(state),
+            NBTTag::TagCompound(ref value) => {
+                let mut entries: Vec<(&String, &NBTTag)> = value.iter().collect();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                entries.hash(state);
+            }
+            NBTTag::TagIntArray(ref value) => value.hash(state),
+            NBTTag::TagLongArray(ref value) => value.hash(state),
+        }
+    }
+}
+
+#[test]
+fn test_tag_names() {
+    assert_eq!(tag_id_name(0), Some("TAG_End"));
+    assert_eq!(tag_id_name(8), Some("TAG_String"));
+    assert_eq!(tag_id_name(12), Some("TAG_Long_Array"));
+    assert_eq!(tag_id_name(13), None);
+    assert_eq!(NBTTag::TagEnd.type_name(), "TAG_End");
+    assert_eq!(NBTTag::TagIntArray(vec![]).type_name(), "TAG_Int_Array");
+}
+
+#[test]
+fn test_deep_size_of() {
+    let tag_size = mem::size_of::<NBTTag>();
+
+    assert_eq!(NBTTag::TagInt(1).deep_size_of(), tag_size);
+    assert_eq!(NBTTag::TagLongArray(Vec::with_capacity(4)).deep_size_of(), tag_size + 32);
+    assert_eq!(NBTTag::TagString("abc".to_owned()).deep_size_of(), tag_size + 3);
+
+    let list = NBTTag::TagList(vec![NBTTag::TagString("abc".to_owned())]);
+    assert_eq!(list.deep_size_of(), 2 * tag_size + 3);
+
+    let compound = from_snbt("{a:\"abc\",b:[I;1,2]}").unwrap();
+    assert!(compound.deep_size_of() >= tag_size + 2 * (2 + tag_size) + 3 + 8);
+}
+
+#[test]
+fn test_eq_ignores_compound_order() {
+    let a = from_snbt("{x:1,y:{a:1b,b:2b}}").unwrap();
+    let b = from_snbt("{y:{b:2b,a:1b},x:1}").unwrap();
+
+    assert_eq!(a, b);
+    assert!(a.eq_unordered(&b));
+    assert!(!a.eq_ordered(&b));
+    assert!(a.eq_ordered(&a.clone()));
+    assert_ne!(from_snbt("[1,2]").unwrap(), from_snbt("[2,1]").unwrap());
+}
+
+#[test]
+fn test_eq_ordered_nested_compounds() {
+    // Only the nested compound is reordered, so comparing the entries of the root is not enough
+    let a = from_snbt("{a:{x:1,y:2}}").unwrap();
+    let b = from_snbt("{a:{y:2,x:1}}").unwrap();
+
+    assert!(a.eq_unordered(&b));
+    assert!(!a.eq_ordered(&b));
+
+    let a = from_snbt("[{x:1,y:2},{z:[{p:1b,q:2b}]}]").unwrap();
+    let b = from_snbt("[{x:1,y:2},{z:[{q:2b,p:1b}]}]").unwrap();
+
+    assert_eq!(a, b);
+    assert!(!a.eq_ordered(&b));
+    assert!(!from_snbt("{x:1}").unwrap().eq_ordered(&from_snbt("{y:1}").unwrap()));
+    assert!(NBTTag::TagDouble(f64::NAN).eq_ordered(&NBTTag::TagDouble(f64::NAN)));
+}
+
+#[test]
+fn test_bit_eq_floats() {
+    assert!(NBTTag::TagDouble(f64::NAN).bit_eq(&NBTTag::TagDouble(f64::NAN)));
+    assert!(NBTTag::TagFloat(f32::NAN).bit_eq(&NBTTag::TagFloat(f32::NAN)));
+    assert!(!NBTTag::TagDouble(0.0).bit_eq(&NBTTag::TagDouble(-0.0)));
+    assert!(!NBTTag::TagDouble(1.0).bit_eq(&NBTTag::TagFloat(1.0)));
+
+    let mut map = IndexMap::new();
+    map.insert("nan".to_owned(), NBTTag::TagList(vec![NBTTag::TagFloat(f32::NAN)]));
+    let tag = NBTTag::TagCompound(map);
+
+    assert!(tag == tag.clone());
+    assert!(tag.bit_eq(&tag.clone()));
+}
+
+#[test]
+fn test_empty_containers() {
+    let empty: Vec<NBTTag> = vec![NBTTag::empty_compound(), NBTTag::empty_list(), NBTTag::empty_byte_array(),
+                                  NBTTag::empty_int_array(), NBTTag::empty_long_array()];
+    let types: Vec<TagType> = empty.iter().map(NBTTag::tag_type).collect();
+
+    assert_eq!(types, vec![TagType::Compound, TagType::List, TagType::ByteArray, TagType::IntArray, TagType::LongArray]);
+    assert_eq!(empty.iter().map(to_snbt).collect::<Vec<_>>(), vec!["{}", "[]", "[B;]", "[I;]", "[L;]"]);
+    assert_eq!(NBTTag::empty_list().list_element_type(), Some(TagType::End));
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut tag = NBTTag::empty_compound();
+
+    assert_eq!(tag.insert("a", NBTTag::TagInt(1)), None);
+    assert_eq!(tag.insert("b", NBTTag::TagInt(2)), None);
+    assert_eq!(tag.insert("c", NBTTag::TagInt(3)), None);
+    assert_eq!(tag.insert("a", NBTTag::TagInt(4)), Some(NBTTag::TagInt(1)));
+    assert_eq!(tag.remove("b"), Some(NBTTag::TagInt(2)));
+    assert_eq!(tag.remove("b"), None);
+
+    let keys: Vec<&String> = tag.compound_iter().unwrap().map(|entry| entry.0).collect();
+    assert_eq!(keys, vec!["a", "c"]);
+
+    let mut int = NBTTag::TagInt(0);
+    assert_eq!(int.insert("a", NBTTag::TagInt(1)), None);
+    assert_eq!(int.remove("a"), None);
+}
+
+#[test]
+fn test_list_helpers() {
+    let mut list = NBTTag::TagList(vec![]);
+
+    list.push(NBTTag::TagShort(1)).unwrap();
+    list.push(NBTTag::TagShort(2)).unwrap();
+    list.push(NBTTag::TagShort(3)).unwrap();
+
+    match list.push(NBTTag::TagInt(4)) {
+        Err(NBTError::UnexpectedType { expected: 2, found: 3 }) => (),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    *list.get_mut(0).unwrap() = NBTTag::TagShort(5);
+
+    assert_eq!(list.get(0), Some(&NBTTag::TagShort(5)));
+    assert_eq!(list.remove(1), Some(NBTTag::TagShort(2)));
+    assert_eq!(list.remove(5), None);
+    assert_eq!(list.get(1), Some(&NBTTag::TagShort(3)));
+    assert_eq!(list.get("a"), None);
+    assert!(NBTTag::TagInt(0).push(NBTTag::TagInt(1)).is_err());
+}
+
+#[test]
+fn test_get_ci() {
+    let mut tag = NBTTag::TagCompound(IndexMap::new());
+    tag.insert("data", NBTTag::TagInt(1));
+    tag.insert("Data", NBTTag::TagInt(2));
+    tag.insert("Level", NBTTag::TagInt(3));
+
+    assert_eq!(tag.get_ci("Data"), Some(&NBTTag::TagInt(2)));
+    assert_eq!(tag.get_ci("DATA"), Some(&NBTTag::TagInt(1)));
+    assert_eq!(tag.get_ci("level"), Some(&NBTTag::TagInt(3)));
+    assert_eq!(tag.get("level"), None);
+    assert_eq!(tag.get_ci("Other"), None);
+}
+
+#[test]
+fn test_compound_iter_keeps_order() {
+    let mut map = IndexMap::new();
+    map.insert("b".to_owned(), NBTTag::TagByte(1));
+    map.insert("a".to_owned(), NBTTag::TagByte(2));
+    map.insert("c".to_owned(), NBTTag::TagByte(3));
+
+    let tag = NBTTag::TagCompound(map);
+    let keys: Vec<&String> = tag.compound_iter().unwrap().map(|entry| entry.0).collect();
+
+    assert_eq!(keys, vec!["b", "a", "c"]);
+    assert!(NBTTag::TagInt(0).compound_iter().is_none());
+}
+
+#[test]
+fn test_hash_ignores_compound_order() {
+    use std::collections::HashSet;
+
+    let mut a = IndexMap::new();
+    a.insert("x".to_owned(), NBTTag::TagDouble(f64::NAN));
+    a.insert("y".to_owned(), NBTTag::TagInt(1));
+
+    let mut b = IndexMap::new();
+    b.insert("y".to_owned(), NBTTag::TagInt(1));
+    b.insert("x".to_owned(), NBTTag::TagDouble(f64::NAN));
+
+    let mut set = HashSet::new();
+    set.insert(NBTTag::TagCompound(a));
+    set.insert(NBTTag::TagCompound(b));
+    set.insert(NBTTag::TagDouble(0.0));
+    set.insert(NBTTag::TagDouble(-0.0));
+
+    assert_eq!(set.len(), 3);
+}
+
+#[test]
+fn test_merge_nested_and_additive() {
+    let mut inner = IndexMap::new();
+    inner.insert("Health".to_owned(), NBTTag::TagFloat(20.0));
+    inner.insert("Food".to_owned(), NBTTag::TagInt(20));
+
+    let mut base = IndexMap::new();
+    base.insert("Name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    base.insert("Stats".to_owned(), NBTTag::TagCompound(inner));
+
+    let mut inner_override = IndexMap::new();
+    inner_override.insert("Health".to_owned(), NBTTag::TagFloat(10.0));
+    inner_override.insert("Xp".to_owned(), NBTTag::TagInt(5));
+
+    let mut overrides = IndexMap::new();
+    overrides.insert("Stats".to_owned(), NBTTag::TagCompound(inner_override));
+    overrides.insert("Mode".to_owned(), NBTTag::TagByte(1));
+
+    let mut tag = NBTTag::TagCompound(base);
+    tag.merge(&NBTTag::TagCompound(overrides)).unwrap();
+
+    let mut expected_inner = IndexMap::new();
+    expected_inner.insert("Health".to_owned(), NBTTag::TagFloat(10.0));
+    expected_inner.insert("Food".to_owned(), NBTTag::TagInt(20));
+    expected_inner.insert("Xp".to_owned(), NBTTag::TagInt(5));
+
+    let mut expected = IndexMap::new();
+    expected.insert("Name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    expected.insert("Stats".to_owned(), NBTTag::TagCompound(expected_inner));
+    expected.insert("Mode".to_owned(), NBTTag::TagByte(1));
+
+    assert_eq!(tag, NBTTag::TagCompound(expected));
+    assert!(NBTTag::TagInt(1).merge(&NBTTag::TagCompound(IndexMap::new())).is_err());
+}
+
+#[test]
+fn test_into_list() {
+    let list = NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(2)]);
+
+    assert_eq!(list.into_list().unwrap(), vec![NBTTag::TagInt(1), NBTTag::TagInt(2)]);
+    assert_eq!(NBTTag::TagInt(1).into_list().unwrap_err().to_string(),
+               "Expected tag of type TAG_List (9) but found TAG_Int (3)");
+}
+
+#[test]
+fn test_array_slices() {
+    let mut tag = NBTTag::TagIntArray(vec![1, 2, 3]);
+
+    tag.as_i32_slice_mut().unwrap()[1] = 5;
+    assert_eq!(tag.as_i32_slice(), Some(&[1, 5, 3][..]));
+    assert_eq!(tag.as_i64_slice(), None);
+    assert_eq!(NBTTag::TagByteArray(vec![-1]).as_i8_slice(), Some(&[-1][..]));
+}
+
+#[test]
+fn test_uuid() {
+    let uuid = 0x069a79f4_44e9_4726_a5be_fca90e38aaf5;
+    let tag = NBTTag::from_uuid(uuid);
+
+    assert_eq!(tag, NBTTag::TagIntArray(vec![0x069a79f4, 0x44e94726, 0xa5befca9u32 as i32, 0x0e38aaf5]));
+    assert_eq!(tag.as_uuid(), Some(uuid));
+    assert_eq!(NBTTag::TagIntArray(vec![1, 2, 3]).as_uuid(), None);
+    assert_eq!(NBTTag::TagLongArray(vec![1, 2, 3, 4]).as_uuid(), None);
+}
+
+#[test]
+fn test_upgrade_uuid_fields() {
+    let mut player = NBTTag::TagCompound(IndexMap::new());
+    player.insert("Health", NBTTag::TagFloat(20.0));
+    player.insert("UUIDMost", NBTTag::TagLong(0x069a79f444e94726));
+    player.insert("UUIDLeast", NBTTag::TagLong(0xa5befca90e38aaf5u64 as i64));
+    player.insert("Score", NBTTag::TagInt(0));
+
+    assert!(player.upgrade_uuid_fields("UUIDMost", "UUIDLeast", "UUID"));
+    assert_eq!(player.compound_iter().unwrap().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["Health", "UUID", "Score"]);
+    assert_eq!(player.get("UUID").and_then(NBTTag::as_uuid), Some(0x069a79f4_44e9_4726_a5be_fca90e38aaf5));
+
+    assert!(!player.upgrade_uuid_fields("UUIDMost", "UUIDLeast", "UUID"));
+}
+
+#[test]
+fn test_rename_key() {
+    let mut tag = from_snbt("{a:1,b:2,c:3}").unwrap();
+
+    tag.rename_key("b", "B").unwrap();
+    assert_eq!(tag.compound_iter().unwrap().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), vec!["a", "B", "c"]);
+    assert_eq!(tag.get("B"), Some(&NBTTag::TagInt(2)));
+
+    assert!(tag.rename_key("b", "d").is_err());
+    assert!(tag.rename_key("a", "c").is_err());
+    assert!(NBTTag::TagInt(1).rename_key("a", "b").is_err());
+}
+
+#[test]
+fn test_get_i64_and_f64() {
+    let tag = from_snbt("{b:1b,s:-2s,i:3,l:4L,f:0.5f,d:1.5d,name:\"x\",list:[7s]}").unwrap();
+
+    let ints: Vec<Option<i64>> = ["b", "s", "i", "l", "f", "name", "missing"].iter().map(|&key| tag.get_i64(key)).collect();
+    assert_eq!(ints, vec![Some(1), Some(-2), Some(3), Some(4), None, None, None]);
+    assert_eq!((tag.get_f64("f"), tag.get_f64("d"), tag.get_f64("i")), (Some(0.5), Some(1.5), None));
+    assert_eq!(tag.get("list").and_then(|list| list.get_i64(0)), Some(7));
+    assert_eq!(NBTTag::TagInt(1).get_i64("b"), None);
+}
+
+#[test]
+fn test_find() {
+    let tag = from_snbt("{Inventory:[{id:\"minecraft:stone\"},{id:\"minecraft:diamond\",tag:{id:\"minecraft:diamond\"}}],EnderItems:[{id:\"minecraft:diamond\"}]}").unwrap();
+    let is_diamond = |key: &str, tag: &NBTTag| key == "id" && *tag == NBTTag::from("minecraft:diamond");
+
+    assert_eq!(tag.find(is_diamond).map(|(path, _)| path), Some("Inventory[1].id".to_owned()));
+    assert_eq!(tag.find_all(is_diamond).into_iter().map(|(path, _)| path).collect::<Vec<_>>(),
+               vec!["Inventory[1].id", "Inventory[1].tag.id", "EnderItems[0].id"]);
+
+    let (path, second) = tag.find(|key, _| key == "1").unwrap();
+    assert_eq!((path.as_str(), second.get("tag").is_some()), ("Inventory[1]", true));
+    assert_eq!(tag.find(|key, _| key == "Count"), None);
+}
+
+#[test]
+fn test_flatten() {
+    let tag = from_snbt("{Pos:[1.0d,2.0d],Inventory:[{id:\"stone\",Count:1b}],Empty:{}}").unwrap();
+
+    let leaves: Vec<(String, NBTTag)> = tag.flatten(false).into_iter().map(|(path, tag)| (path, tag.clone())).collect();
+    assert_eq!(leaves, vec![
+        ("Pos[0]".to_owned(), NBTTag::TagDouble(1.0)),
+        ("Pos[1]".to_owned(), NBTTag::TagDouble(2.0)),
+        ("Inventory[0].id".to_owned(), NBTTag::TagString("stone".to_owned())),
+        ("Inventory[0].Count".to_owned(), NBTTag::TagByte(1)),
+    ]);
+
+    let paths: Vec<String> = tag.flatten(true).into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths, vec!["Pos", "Pos[0]", "Pos[1]", "Inventory", "Inventory[0]", "Inventory[0].id", "Inventory[0].Count", "Empty"]);
+
+    // Keys containing separators are quoted, so every path leads back to its tag
+    let mut tag = from_snbt("{\"a.b\":{\"[0]\":1,\"*\":2},a:{b:3}}").unwrap();
+    let leaves: Vec<(String, NBTTag)> = tag.flatten(false).into_iter().map(|(path, tag)| (path, tag.clone())).collect();
+    assert_eq!(leaves.iter().map(|leaf| leaf.0.as_str()).collect::<Vec<_>>(), vec!["\"a.b\".\"[0]\"", "\"a.b\".\"*\"", "a.b"]);
+
+    for (path, leaf) in leaves {
+        let mut found = Vec::new();
+        tag.for_each_at(&path, |tag| found.push(tag.clone()));
+        assert_eq!(found, vec![leaf]);
+    }
+}
+
+#[test]
+fn test_coerce_int() {
+    assert_eq!(NBTTag::TagInt(3).coerce_int(TagType::Long).unwrap(), NBTTag::TagLong(3));
+    assert_eq!(NBTTag::TagLong(-3).coerce_int(TagType::Byte).unwrap(), NBTTag::TagByte(-3));
+    assert_eq!(NBTTag::TagShort(300).coerce_int(TagType::Byte).unwrap_err().to_string(), "300 does not fit in a TAG_Byte");
+    assert!(NBTTag::TagFloat(1.0).coerce_int(TagType::Int).is_err());
+    assert!(NBTTag::TagInt(1).coerce_int(TagType::Float).is_err());
+}
+
+#[test]
+fn test_ord() {
+    let mut tags = vec![
+        NBTTag::TagString("b".to_owned()),
+        NBTTag::TagDouble(f64::NAN),
+        NBTTag::TagByte(5),
+        NBTTag::TagDouble(-0.0),
+        NBTTag::TagString("a".to_owned()),
+        NBTTag::TagDouble(f64::NEG_INFINITY),
+        NBTTag::TagDouble(0.0),
+        NBTTag::TagByte(-1),
+    ];
+    tags.sort();
+
+    assert_eq!(tags, vec![
+        NBTTag::TagByte(-1),
+        NBTTag::TagByte(5),
+        NBTTag::TagDouble(f64::NEG_INFINITY),
+        NBTTag::TagDouble(-0.0),
+        NBTTag::TagDouble(0.0),
+        NBTTag::TagDouble(f64::NAN),
+        NBTTag::TagString("a".to_owned()),
+        NBTTag::TagString("b".to_owned()),
+    ]);
+
+    let mut a = NBTTag::TagCompound(IndexMap::new());
+    a.insert("x", NBTTag::TagInt(1));
+    a.insert("y", NBTTag::TagInt(2));
+
+    let mut b = NBTTag::TagCompound(IndexMap::new());
+    b.insert("y", NBTTag::TagInt(2));
+    b.insert("x", NBTTag::TagInt(1));
+
+    assert_eq!(a.cmp(&b), Ordering::Equal);
+
+    b.insert("x", NBTTag::TagInt(3));
+
+    assert!(a < b);
+}
+
+#[test]
+fn test_canonicalize() {
+    let mut inner = NBTTag::TagCompound(IndexMap::new());
+    inner.insert("b", NBTTag::TagInt(1));
+    inner.insert("a", NBTTag::TagInt(2));
+
+    let mut a = NBTTag::TagCompound(IndexMap::new());
+    a.insert("z", NBTTag::TagList(vec![inner.clone()]));
+    a.insert("y", inner.clone());
+
+    let mut b = NBTTag::TagCompound(IndexMap::new());
+    b.insert("y", inner.clone());
+    b.insert("z", NBTTag::TagList(vec![inner]));
+
+    assert_ne!(write::write_headless(&a).unwrap(), write::write_headless(&b).unwrap());
+
+    a.canonicalize();
+    b.canonicalize();
+
+    assert_eq!(write::write_headless(&a).unwrap(), write::write_headless(&b).unwrap());
+    assert_eq!(a.compound_iter().unwrap().map(|entry| entry.0.as_str()).collect::<Vec<_>>(), vec!["y", "z"]);
+    assert_eq!(a.get("y").unwrap().compound_iter().unwrap().map(|entry| entry.0.as_str()).collect::<Vec<_>>(),
+               vec!["a", "b"]);
+}
+
+#[test]
+fn test_apply_snbt_patch() {
+    let mut tag = from_snbt("{id: \"minecraft:diamond_sword\", Count: 1b, tag: {Damage: 10}}").unwrap();
+
+    tag.apply_snbt_patch("{Count: 2b, tag: {Unbreakable: 1b}}").unwrap();
+
+    assert_eq!(tag, from_snbt("{id: \"minecraft:diamond_sword\", Count: 2b, tag: {Damage: 10, Unbreakable: 1b}}").unwrap());
+    assert_eq!(tag.apply_snbt_patch("{Count: }").unwrap_err().to_string(), "Invalid SNBT at position 8: Expected value");
+    assert!(tag.apply_snbt_patch("[1, 2]").is_err());
+}
+
+#[test]
+fn test_for_each_at() {
+    let mut player = from_snbt("{Inventory: [{id: \"a\", Count: 5b}, {id: \"b\", Count: 1b}, {id: \"c\"}]}").unwrap();
+
+    player.for_each_at("Inventory.*.Count", |count| {
+        if let NBTTag::TagByte(ref mut value) = *count {
+            *value -= 1;
+        }
+    });
+
+    assert_eq!(player, from_snbt("{Inventory: [{id: \"a\", Count: 4b}, {id: \"b\", Count: 0b}, {id: \"c\"}]}").unwrap());
+
+    let mut ids = Vec::new();
+    player.for_each_at("Inventory[1].id", |id| ids.push(id.clone()));
+    player.for_each_at("Inventory[7].id", |id| ids.push(id.clone()));
+
+    assert_eq!(ids, vec![NBTTag::TagString("b".to_owned())]);
+}
+
+#[test]
+fn test_array_conversions() {
+    let bytes = NBTTag::TagList(vec![NBTTag::TagByte(1), NBTTag::TagByte(-2)]);
+    let ints = NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(-2)]);
+    let longs = NBTTag::TagList(vec![NBTTag::TagLong(1), NBTTag::TagLong(-2)]);
+
+    assert_eq!(bytes.list_to_byte_array().unwrap(), NBTTag::TagByteArray(vec![1, -2]));
+    assert_eq!(ints.list_to_int_array().unwrap(), NBTTag::TagIntArray(vec![1, -2]));
+    assert_eq!(longs.list_to_long_array().unwrap(), NBTTag::TagLongArray(vec![1, -2]));
+    assert_eq!(NBTTag::TagList(vec![]).list_to_int_array().unwrap(), NBTTag::TagIntArray(vec![]));
+
+    assert_eq!(NBTTag::TagByteArray(vec![1, -2]).byte_array_to_list().unwrap(), bytes);
+    assert_eq!(NBTTag::TagIntArray(vec![1, -2]).int_array_to_list().unwrap(), ints);
+    assert_eq!(NBTTag::TagLongArray(vec![1, -2]).long_array_to_list().unwrap(), longs);
+
+    assert_eq!(ints.list_to_byte_array().unwrap_err().to_string(),
+               "Expected tag of type TAG_Byte (1) but found TAG_Int (3)");
+    assert!(NBTTag::TagInt(1).list_to_int_array().is_err());
+    assert!(NBTTag::TagIntArray(vec![]).long_array_to_list().is_err());
+}
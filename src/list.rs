@@ -0,0 +1,85 @@
+use error::NBTError;
+use indexmap::IndexMap;
+use write::get_tag_id;
+use NBTTag;
+
+// Rust types that correspond to a single tag type, used to pick the element type of a ListBuilder
+pub trait ListElement {
+    fn tag_id() -> u8;
+}
+
+macro_rules! list_element (($t:ty, $id:expr) => (
+    impl ListElement for $t {
+        fn tag_id() -> u8 {
+            $id
+        }
+    }
+););
+
+list_element!(i8, 1);
+list_element!(i16, 2);
+list_element!(i32, 3);
+list_element!(i64, 4);
+list_element!(f32, 5);
+list_element!(f64, 6);
+list_element!(Vec<i8>, 7);
+list_element!(String, 8);
+list_element!(Vec<NBTTag>, 9);
+list_element!(IndexMap<String, NBTTag>, 10);
+list_element!(Vec<i32>, 11);
+list_element!(Vec<i64>, 12);
+
+// Builds a TagList whose elements are all of the same type
+#[derive(Debug, Clone)]
+pub struct ListBuilder {
+    tag_id: u8,
+    elems: Vec<NBTTag>,
+}
+
+impl ListBuilder {
+    pub fn new<T: ListElement>() -> ListBuilder {
+        ListBuilder::with_tag_id(T::tag_id())
+    }
+
+    pub fn with_tag_id(tag_id: u8) -> ListBuilder {
+        ListBuilder {
+            tag_id,
+            elems: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, tag: NBTTag) -> Result<&mut ListBuilder, NBTError> {
+        match get_tag_id(&tag) {
+            tag_id if tag_id == self.tag_id => {
+                self.elems.push(tag);
+                Ok(self)
+            }
+            found => Err(NBTError::UnexpectedType {
+                expected: self.tag_id,
+                found,
+            }),
+        }
+    }
+
+    pub fn build(self) -> NBTTag {
+        NBTTag::TagList(self.elems)
+    }
+}
+
+#[test]
+fn test_list_builder() {
+    let mut builder = ListBuilder::new::<i32>();
+    builder.push(NBTTag::TagInt(1)).unwrap().push(NBTTag::TagInt(2)).unwrap();
+
+    assert_eq!(builder.push(NBTTag::TagString("3".to_owned())).unwrap_err().to_string(),
+               "Expected tag of type TAG_Int (3) but found TAG_String (8)");
+    assert_eq!(builder.build(), NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(2)]));
+}
+
+#[test]
+fn test_list_builder_from_tag_id() {
+    let mut builder = ListBuilder::with_tag_id(10);
+
+    assert!(builder.push(NBTTag::TagCompound(IndexMap::new())).is_ok());
+    assert!(builder.push(NBTTag::TagList(vec![])).is_err());
+}
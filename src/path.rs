@@ -0,0 +1,133 @@
+// Helpers for building the dotted/bracketed paths used to address tags, e.g. `Inventory[0].id`.
+// Keys that would be ambiguous unquoted, like `a.b`, are written in double quotes, with `"` and `\`
+// escaped by a backslash: `Inventory[0]."a.b"`.
+
+use std::borrow::Cow;
+
+// A single step of a path, as split by segments
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment<'a> {
+    // A key of a compound, or the index of a list element written like one
+    Key(Cow<'a, str>),
+    // An unquoted `*`, which matches every element of a list or every value of a compound
+    Any,
+}
+
+pub fn child_key(parent: &str, key: &str) -> String {
+    let key = if needs_quotes(key) {
+        Cow::Owned(format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\"")))
+    } else {
+        Cow::Borrowed(key)
+    };
+
+    if parent.is_empty() {
+        key.into_owned()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+pub fn child_index(parent: &str, index: usize) -> String {
+    format!("{}[{}]", parent, index)
+}
+
+// Keys that are empty, are the wildcard or contain a separator or quote can't be written bare
+fn needs_quotes(key: &str) -> bool {
+    key.is_empty() || key == "*" || key.contains(['.', '[', ']', '"'])
+}
+
+// Splits a path into its keys and indices, e.g. `Inventory[0].id` into `Inventory`, `0` and `id`.
+// Indices may also be written as keys, like `Inventory.0.id`, and quoted keys are unescaped.
+pub fn segments(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+
+    while let Some(first) = rest.chars().next() {
+        let (segment, len) = match first {
+            '.' => (None, 1),
+            '[' => {
+                let end = rest.find(']').unwrap_or(rest.len());
+                (bare_segment(&rest[1..end]), (end + 1).min(rest.len()))
+            }
+            '"' => {
+                let (key, len) = unquote(&rest[1..]);
+                (Some(Segment::Key(key)), 1 + len)
+            }
+            _ => {
+                let end = rest.find(['.', '[']).unwrap_or(rest.len());
+                (bare_segment(&rest[..end]), end)
+            }
+        };
+
+        segments.extend(segment);
+        rest = &rest[len..];
+    }
+
+    segments
+}
+
+fn bare_segment(segment: &str) -> Option<Segment<'_>> {
+    match segment {
+        "" => None,
+        "*" => Some(Segment::Any),
+        key => Some(Segment::Key(Cow::Borrowed(key))),
+    }
+}
+
+// Reads a quoted key up to and including its closing quote, returning it and the number of bytes
+// it took up. A key without a closing quote runs to the end of the path.
+fn unquote(input: &str) -> (Cow<'_, str>, usize) {
+    let mut key = String::new();
+    let mut escaped = false;
+
+    for (index, c) in input.char_indices() {
+        match c {
+            _ if escaped => {
+                key.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            '"' if key.len() == index => return (Cow::Borrowed(&input[..index]), index + 1),
+            '"' => return (Cow::Owned(key), index + 1),
+            _ => key.push(c),
+        }
+    }
+
+    (Cow::Owned(key), input.len())
+}
+
+#[cfg(test)]
+fn keys<'a>(segments: &'a [Segment]) -> Vec<&'a str> {
+    segments.iter().map(|segment| match *segment {
+        Segment::Key(ref key) => key.as_ref(),
+        Segment::Any => "*",
+    }).collect()
+}
+
+#[test]
+fn test_child_paths() {
+    assert_eq!(child_key("", "Inventory"), "Inventory");
+    assert_eq!(child_index("Inventory", 3), "Inventory[3]");
+    assert_eq!(child_key("Inventory[3]", "id"), "Inventory[3].id");
+    assert_eq!(child_key("a", "b.c"), "a.\"b.c\"");
+    assert_eq!(child_key("", "say \"hi\""), "\"say \\\"hi\\\"\"");
+}
+
+#[test]
+fn test_segments() {
+    assert_eq!(keys(&segments("Inventory[3].id")), vec!["Inventory", "3", "id"]);
+    assert_eq!(keys(&segments("Inventory.*.Count")), vec!["Inventory", "*", "Count"]);
+    assert_eq!(keys(&segments("a[*][1]")), vec!["a", "*", "1"]);
+    assert!(segments("").is_empty());
+
+    assert_eq!(segments("a.*.\"*\""), vec![Segment::Key("a".into()), Segment::Any, Segment::Key("*".into())]);
+}
+
+#[test]
+fn test_quoted_keys_round_trip() {
+    for key in &["b.c", "[0]", "a]", "say \"hi\"", "back\\slash\"", "", "*", "plain"] {
+        let path = child_index(&child_key("root", key), 2);
+
+        assert_eq!(segments(&path), vec![Segment::Key("root".into()), Segment::Key((*key).into()), Segment::Key("2".into())]);
+    }
+}
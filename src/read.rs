@@ -1,33 +1,215 @@
-use file::NBTFile;
+use compression::Compression;
+use error::NBTError;
+use file::{NBTFile, NBTFileRef};
+use tag_ref::NBTTagRef;
+use Endianness;
 use NBTTag;
+use TagType;
 use nom;
 use nom::{
-    be_i16,
-    be_i32,
-    be_i64,
     be_i8,
-    be_u16,
-    Endianness,
+    be_u8,
     ErrorKind,
     IResult,
-    le_i16,
-    le_i32,
-    le_i64,
-    le_u16,
 };
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::mem;
+use std::ops::Deref;
 use std::str;
+use std::task::Poll;
 
-macro_rules! f32 ( ($i:expr, $e:expr) => ( {if nom::Endianness::Big == $e { nom::be_f32($i) } else { nom::le_f32($i) } } ););
-macro_rules! f64 ( ($i:expr, $e:expr) => ( {if nom::Endianness::Big == $e { nom::be_f64($i) } else { nom::le_f64($i) } } ););
+// These shadow the macros of nom, so that parsers take the Endianness of this crate
+macro_rules! u16 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_u16($i) } else { nom::le_u16($i) } } ););
+macro_rules! i16 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_i16($i) } else { nom::le_i16($i) } } ););
+macro_rules! i32 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_i32($i) } else { nom::le_i32($i) } } ););
+macro_rules! i64 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_i64($i) } else { nom::le_i64($i) } } ););
+macro_rules! f32 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_f32($i) } else { nom::le_f32($i) } } ););
+macro_rules! f64 ( ($i:expr, $e:expr) => ( {if Endianness::Big == $e { nom::be_f64($i) } else { nom::le_f64($i) } } ););
 
-named!(read_tag_name<&[u8], &str>,
-    do_parse!(
-        len:  u16!(nom::Endianness::Big) >>
-        name: take!(len)                 >>
-        (str::from_utf8(name).unwrap())
+// Errors raised while reading a tag value carry ErrorKind::Custom(TAG_CONTEXT + tag type), so the
+// type of the innermost tag can be reported
+const TAG_CONTEXT: u32 = 16;
+
+// ErrorKind::Custom(LIMIT_CONTEXT + tag type) is raised at the length of an array or list that is
+// longer than ReadOptions::max_len
+const LIMIT_CONTEXT: u32 = 32;
+
+// ErrorKind::Custom(NEGATIVE_CONTEXT + tag type) is raised at the length of an array or list that is
+// negative
+const NEGATIVE_CONTEXT: u32 = 48;
+
+// ErrorKind::Custom(EOF_CONTEXT + tag type) is raised at the first element of an array that the
+// input ends before the last element of
+const EOF_CONTEXT: u32 = 64;
+
+// Compounds and lists may be nested at most this deep by default, like in Minecraft itself. This
+// keeps malicious input from overflowing the stack.
+pub(crate) const MAX_DEPTH: usize = 512;
+
+// ErrorKind::Custom code of a compound that contains the same key twice while the duplicate key
+// policy is DuplicateKey::Error. The position of the error is the start of the second entry.
+const DUPLICATE_KEY: u32 = 2;
+
+// ErrorKind::Custom(INCOMPLETE_CONTEXT + tag type) replaces the Incomplete of nom where the input
+// ends inside a tag, so the tag can be reported while truncated input is still told apart
+const INCOMPLETE_CONTEXT: u32 = 80;
+
+// ErrorKind::Custom code raised where the running total of allocated bytes first exceeds
+// ReadOptions::max_total_bytes
+const BUDGET_EXCEEDED: u32 = 3;
+
+// What to do when a compound contains the same key more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKey {
+    Error,
+    KeepFirst,
+    KeepLast,
+}
+
+// How strings and names are decoded. Java edition writes the modified UTF-8 of Java's DataOutput,
+// which encodes the null character as two bytes and other characters outside the BMP as surrogate
+// pairs. Mutf8 accepts both that and regular UTF-8, Utf8 only the latter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringEncoding {
+    Utf8,
+    Mutf8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOptions {
+    pub endianness: Endianness,
+    // The compression of the input, or None to detect it from the first bytes
+    pub compression: Option<Compression>,
+    // Compounds and lists may be nested at most this deep
+    pub max_depth: usize,
+    // Maximum number of elements of an array or list
+    pub max_len: usize,
+    pub on_duplicate_key: DuplicateKey,
+    pub string_encoding: StringEncoding,
+    // Accept files with another root than a TagCompound, like the TagList roots some tools write
+    pub allow_non_compound_root: bool,
+    // Fail when bytes follow the root of a file, instead of ignoring them
+    pub reject_trailing: bool,
+    // Maximum number of bytes that the arrays, strings, names and lists of a single read may
+    // allocate together, so that many lengths below max_len can't add up to an unbounded amount
+    pub max_total_bytes: usize,
+}
+
+// The defaults match Java edition files
+impl Default for ReadOptions {
+    fn default() -> ReadOptions {
+        ReadOptions {
+            endianness: Endianness::Big,
+            compression: None,
+            max_depth: MAX_DEPTH,
+            max_len: i32::MAX as usize,
+            on_duplicate_key: DuplicateKey::KeepLast,
+            string_encoding: StringEncoding::Mutf8,
+            allow_non_compound_root: false,
+            reject_trailing: false,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+// The options of a single read, together with the number of bytes it has allocated so far
+struct ReadState<'o> {
+    options: &'o ReadOptions,
+    used: Cell<usize>,
+}
+
+impl<'o> ReadState<'o> {
+    fn new(options: &'o ReadOptions) -> ReadState<'o> {
+        ReadState { options, used: Cell::new(0) }
+    }
+
+    // Like parse_error, but reporting a failure raised by charge as BudgetExceeded
+    fn error(&self, bytes: &[u8], err: nom::Err<&[u8]>) -> NBTError {
+        match err {
+            nom::Err::Failure(nom::Context::Code(_, ErrorKind::Custom(BUDGET_EXCEEDED))) => {
+                NBTError::BudgetExceeded { total: self.used.get(), max: self.options.max_total_bytes }
+            }
+            err => parse_error(bytes, err, self.options),
+        }
+    }
+}
+
+impl<'o> Deref for ReadState<'o> {
+    type Target = ReadOptions;
+
+    fn deref(&self) -> &ReadOptions {
+        self.options
+    }
+}
+
+// Adds bytes to the total allocated by the read, failing once it exceeds options.max_total_bytes.
+// Called before allocating, so a huge length fails before the memory is requested.
+fn charge<'a>(input: &'a [u8], state: &ReadState, bytes: usize) -> IResult<&'a [u8], ()> {
+    let used = state.used.get().saturating_add(bytes);
+    state.used.set(used);
+
+    if used > state.max_total_bytes {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(BUDGET_EXCEEDED))));
+    }
+
+    Ok((input, ()))
+}
+
+// Reads the name of a tag, honoring the endianness and string encoding of options
+fn read_name<'a>(input: &'a [u8], options: &ReadOptions) -> IResult<&'a [u8], Cow<'a, str>> {
+    do_parse!(input,
+        len:  u16!(options.endianness) >>
+        name: map_opt!(take!(len), |bytes| decode_string(bytes, options.string_encoding)) >>
+        (name)
     )
-);
+}
+
+fn decode_string(bytes: &[u8], encoding: StringEncoding) -> Option<Cow<'_, str>> {
+    match str::from_utf8(bytes) {
+        Ok(value) => Some(Cow::Borrowed(value)),
+        Err(_) if encoding == StringEncoding::Mutf8 => decode_mutf8(bytes).map(Cow::Owned),
+        Err(_) => None,
+    }
+}
+
+// Decodes modified UTF-8 by collecting UTF-16 code units, so that surrogate pairs encoded as two
+// three byte sequences are joined. Four byte sequences of regular UTF-8 are accepted as well.
+fn decode_mutf8(bytes: &[u8]) -> Option<String> {
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    let continuation = |index: usize| match bytes.get(index) {
+        Some(&byte) if byte & 0xC0 == 0x80 => Some(u32::from(byte & 0x3F)),
+        _ => None,
+    };
+
+    while i < bytes.len() {
+        let byte = u32::from(bytes[i]);
+
+        let (code, len) = match byte {
+            0x00..=0x7F => (byte, 1),
+            0xC0..=0xDF => ((byte & 0x1F) << 6 | continuation(i + 1)?, 2),
+            0xE0..=0xEF => ((byte & 0x0F) << 12 | continuation(i + 1)? << 6 | continuation(i + 2)?, 3),
+            0xF0..=0xF7 => {
+                ((byte & 0x07) << 18 | continuation(i + 1)? << 12 | continuation(i + 2)? << 6 | continuation(i + 3)?, 4)
+            }
+            _ => return None,
+        };
+
+        if code > 0xFFFF {
+            let mut pair = [0; 2];
+            units.extend_from_slice(char::from_u32(code)?.encode_utf16(&mut pair));
+        } else {
+            units.push(code as u16);
+        }
+
+        i += len;
+    }
+
+    String::from_utf16(&units).ok()
+}
 
 named!(read_tag_byte<&[u8], NBTTag>,
     do_parse!(
@@ -36,163 +218,704 @@ named!(read_tag_byte<&[u8], NBTTag>,
     )
 );
 
-named!(read_tag_short<&[u8], NBTTag>,
+named_args!(read_tag_short(endianness: Endianness)<NBTTag>,
     do_parse!(
-        val: i16!(nom::Endianness::Big) >>
+        val: i16!(endianness) >>
         (NBTTag::TagShort(val))
     )
 );
 
-named!(read_tag_int<&[u8], NBTTag>,
+named_args!(read_tag_int(endianness: Endianness)<NBTTag>,
     do_parse!(
-        val: i32!(nom::Endianness::Big) >>
+        val: i32!(endianness) >>
         (NBTTag::TagInt(val))
     )
 );
 
-named!(read_tag_long<&[u8], NBTTag>,
+named_args!(read_tag_long(endianness: Endianness)<NBTTag>,
     do_parse!(
-        val: i64!(nom::Endianness::Big) >>
+        val: i64!(endianness) >>
         (NBTTag::TagLong(val))
     )
 );
 
-named!(read_tag_float<&[u8], NBTTag>,
+named_args!(read_tag_float(endianness: Endianness)<NBTTag>,
     do_parse!(
-        val: f32!(nom::Endianness::Big) >>
+        val: f32!(endianness) >>
         (NBTTag::TagFloat(val))
     )
 );
 
-named!(read_tag_double<&[u8], NBTTag>,
+named_args!(read_tag_double(endianness: Endianness)<NBTTag>,
     do_parse!(
-        val: f64!(nom::Endianness::Big) >>
+        val: f64!(endianness) >>
         (NBTTag::TagDouble(val))
     )
 );
 
-named!(read_tag_byte_array<&[u8], NBTTag>,
-    do_parse!(
-        len: i32!(nom::Endianness::Big)        >>
-        val: many_m_n!(1, len as usize, be_i8) >>
+// Reads the length of an array or list, which may not be negative or exceed options.max_len.
+// Lengths are checked before they are cast, as a negative one would otherwise become a huge usize.
+fn read_len<'a>(input: &'a [u8], tag_type: u8, options: &ReadOptions) -> IResult<&'a [u8], usize> {
+    let (rest, len) = i32!(input, options.endianness)?;
+
+    if len < 0 {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(NEGATIVE_CONTEXT + u32::from(tag_type)))));
+    }
+
+    if len as usize > options.max_len {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(LIMIT_CONTEXT + u32::from(tag_type)))));
+    }
+
+    Ok((rest, len as usize))
+}
+
+// Fails unless input holds the len elements of an array, each size bytes long, so that a truncated
+// array is reported along with how much of it there is
+fn require_elements(input: &[u8], tag_type: u8, len: usize, size: usize) -> IResult<&[u8], ()> {
+    if input.len() / size < len {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(EOF_CONTEXT + u32::from(tag_type)))));
+    }
+
+    Ok((input, ()))
+}
+
+fn read_tag_byte_array<'a>(input: &'a [u8], options: &ReadState) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        len: apply!(read_len, 7, options)    >>
+        apply!(require_elements, 7, len, 1) >>
+        apply!(charge, options, len)         >>
+        val: count!(be_i8, len)              >>
         (NBTTag::TagByteArray(val))
     )
-);
+}
 
-named!(read_tag_string<&[u8], NBTTag>,
-    do_parse!(
-        len: u16!(nom::Endianness::Big) >>
-        val: take!(len)                 >>
-        (NBTTag::TagString(str::from_utf8(val).unwrap().to_owned()))
+fn read_tag_string<'a>(input: &'a [u8], options: &ReadState) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        val: apply!(read_name, options)       >>
+        apply!(charge, options, val.len()) >>
+        (NBTTag::TagString(val.into_owned()))
     )
-);
+}
 
-named!(read_tag_list<&[u8], NBTTag>,
-    do_parse!(
-        elems_type: take!(1) >>
-        len: i32!(nom::Endianness::Big) >>
-        elems: many_m_n!(1, len as usize, apply!(read_tag_known, elems_type[0])) >>
+fn read_tag_list<'a>(input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        elems_type: be_u8 >>
+        len: apply!(read_len, 9, options) >>
+        apply!(charge, options, len.saturating_mul(mem::size_of::<NBTTag>())) >>
+        elems: count!(apply!(read_tag_known, elems_type, options, depth + 1), len) >>
         (NBTTag::TagList(elems))
     )
-);
+}
 
-named!(read_tag_compound<&[u8], NBTTag>,
-    do_parse!(
-        elems: many_till!(read_tag, tag!([0x00])) >>
-        (NBTTag::TagCompound(tuple_vector_to_hash_map(elems.0)))
-    )
-);
+fn read_tag_compound<'a>(mut input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTag> {
+    let mut map = IndexMap::new();
 
-named!(read_tag_int_array<&[u8], NBTTag>,
-    do_parse!(
-        len: i32!(nom::Endianness::Big)         >>
-        val: many_m_n!(1, len as usize, i32!(nom::Endianness::Big)) >>
+    loop {
+        match input.first() {
+            Some(0x00) => return Ok((&input[1..], NBTTag::TagCompound(map))),
+            Some(_) => {}
+            None => return Err(nom::Err::Incomplete(nom::Needed::Size(1))),
+        }
+
+        let (rest, (name, tag)) = read_tag(input, options, depth + 1)?;
+        charge(input, options, name.len())?;
+
+        if map.contains_key(name.as_ref()) {
+            match options.on_duplicate_key {
+                DuplicateKey::Error => {
+                    return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(DUPLICATE_KEY))));
+                }
+                DuplicateKey::KeepFirst => {}
+                DuplicateKey::KeepLast => {
+                    map.insert(name.into_owned(), tag);
+                }
+            }
+        } else {
+            map.insert(name.into_owned(), tag);
+        }
+
+        input = rest;
+    }
+}
+
+fn read_tag_int_array<'a>(input: &'a [u8], options: &ReadState) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        len: apply!(read_len, 11, options)               >>
+        apply!(require_elements, 11, len, 4)            >>
+        apply!(charge, options, len.saturating_mul(4))   >>
+        val: count!(i32!(options.endianness), len)      >>
         (NBTTag::TagIntArray(val))
     )
-);
+}
 
-named!(read_tag_long_array<&[u8], NBTTag>,
-    do_parse!(
-        len: i32!(nom::Endianness::Big)         >>
-        val: many_m_n!(1, len as usize, i64!(nom::Endianness::Big)) >>
+fn read_tag_long_array<'a>(input: &'a [u8], options: &ReadState) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        len: apply!(read_len, 12, options)               >>
+        apply!(require_elements, 12, len, 8)            >>
+        apply!(charge, options, len.saturating_mul(8))   >>
+        val: count!(i64!(options.endianness), len)      >>
         (NBTTag::TagLongArray(val))
     )
-);
+}
 
-named!(read_tag<&[u8], (&str, NBTTag)>,
-    do_parse!(
-        tag_type: take!(1)                          >>
-        name: read_tag_name                         >>
-        output: apply!(read_tag_known, tag_type[0]) >>
+fn read_tag<'a>(input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], (Cow<'a, str>, NBTTag)> {
+    do_parse!(input,
+        tag_type: be_u8                                          >>
+        name: apply!(read_name, options)                         >>
+        output: apply!(read_tag_known, tag_type, options, depth) >>
         (name, output)
     )
-);
+}
 
-named!(pub read_nbt_file<&[u8], Option<NBTFile>>,
-    do_parse!(
-        root: read_tag >>
-        (file_from_tuple(root))
+// Reads a file, returning it and the bytes following its root, or None if the root is not a
+// TagCompound while options don't allow that
+pub(crate) fn read_nbt_file<'a>(input: &'a [u8], options: &ReadOptions) -> Result<(&'a [u8], Option<NBTFileRef<'a>>), NBTError> {
+    let state = ReadState::new(options);
+
+    match read_tag(input, &state, 0) {
+        Ok((rest, root)) => Ok((rest, file_from_tuple(root, options))),
+        Err(err) => Err(state.error(input, err)),
+    }
+}
+
+fn read_headless_tag<'a>(input: &'a [u8], options: &ReadState) -> IResult<&'a [u8], NBTTag> {
+    do_parse!(input,
+        tag_type: be_u8                                     >>
+        output: apply!(read_tag_known, tag_type, options, 0) >>
+        (output)
     )
-);
+}
+
+// Reads a tag that consists of just a type id and a value, without the name that normally follows
+// the type id, like the root of some network and schematic palette blobs
+pub fn read_headless_nbt(bytes: &[u8]) -> Result<NBTTag, NBTError> {
+    let options = ReadOptions::default();
+    let state = ReadState::new(&options);
+
+    match read_headless_tag(bytes, &state) {
+        Ok((_, tag)) => Ok(tag),
+        Err(err) => Err(state.error(bytes, err)),
+    }
+}
+
+// Reads just the value of a tag of type tag_id, without type id or name, returning it and the
+// number of bytes it took up. The inverse of write::encode_value.
+pub fn decode_value(bytes: &[u8], tag_id: u8, endianness: Endianness) -> Result<(NBTTag, usize), NBTError> {
+    let options = ReadOptions { endianness, ..ReadOptions::default() };
+    let state = ReadState::new(&options);
+
+    match read_tag_known(bytes, tag_id, &state, 0) {
+        Ok((rest, tag)) => Ok((tag, bytes.len() - rest.len())),
+        Err(err) => Err(state.error(bytes, err)),
+    }
+}
+
+// Reads the little endian tags that Bedrock Edition stores back to back in a single LevelDB value,
+// like the block entities of a chunk. Strings are UTF-8, as Bedrock does not use MUTF-8. A record
+// cut off by the end of bytes is an error at the offset where bytes end.
+pub fn read_bedrock_records(bytes: &[u8]) -> Result<Vec<NBTTag>, NBTError> {
+    let options = ReadOptions {
+        endianness: Endianness::Little,
+        string_encoding: StringEncoding::Utf8,
+        ..ReadOptions::default()
+    };
+    let state = ReadState::new(&options);
+    let mut records = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        match read_tag(rest, &state, 0) {
+            Ok((next, (_, tag))) => {
+                records.push(tag);
+                rest = next;
+            }
+            Err(err) => return Err(state.error(bytes, err)),
+        }
+    }
+
+    Ok(records)
+}
+
+// Returns the number of bytes the value of a tag of type tag_id at the start of bytes takes up,
+// like decode_value but without building the tag
+pub fn skip_value(bytes: &[u8], tag_id: u8, endianness: Endianness) -> Result<usize, NBTError> {
+    let options = ReadOptions { endianness, ..ReadOptions::default() };
+
+    match skip_tag_known(bytes, tag_id, &options, 0) {
+        Ok((rest, ())) => Ok(bytes.len() - rest.len()),
+        Err(err) => Err(parse_error(bytes, err, &options)),
+    }
+}
+
+// Reads the value of a single entry of the root compound of an uncompressed file, skipping over
+// the other entries without reading their values. Returns None if the root has no such entry. A
+// key that occurs more than once is resolved by options.on_duplicate_key like a full read does,
+// so the entries after a match are still scanned unless the first one is kept.
+// options.compression is ignored.
+pub fn read_root_field(bytes: &[u8], key: &str, options: &ReadOptions) -> Result<Option<NBTTag>, NBTError> {
+    match bytes.first() {
+        Some(&found) if found != 10 => return Err(NBTError::InvalidRoot { found }),
+        _ => (),
+    }
+
+    let state = ReadState::new(options);
+
+    match find_root_field(bytes, key, &state) {
+        Ok((_, tag)) => Ok(tag),
+        Err(err) => Err(state.error(bytes, err)),
+    }
+}
+
+fn find_root_field<'a>(input: &'a [u8], key: &str, options: &ReadState) -> IResult<&'a [u8], Option<NBTTag>> {
+    let (mut input, _) = do_parse!(input, be_u8 >> name: apply!(read_name, options) >> (name))?;
+    let mut found = None;
+
+    loop {
+        let (rest, tag_type) = with_tag_context(input, 10, be_u8(input))?;
+
+        if tag_type == 0 {
+            return Ok((rest, found));
+        }
+
+        let (rest, name) = with_tag_context(rest, 10, read_name(rest, options))?;
+
+        if name != key {
+            input = skip_tag_known(rest, tag_type, options, 1)?.0;
+            continue;
+        }
+
+        match options.on_duplicate_key {
+            DuplicateKey::Error if found.is_some() => {
+                return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(DUPLICATE_KEY))));
+            }
+            DuplicateKey::KeepFirst => return read_tag_known(rest, tag_type, options, 1).map(|(rest, tag)| (rest, Some(tag))),
+            _ => {
+                let (rest, tag) = read_tag_known(rest, tag_type, options, 1)?;
+                found = Some(tag);
+                input = rest;
+            }
+        }
+    }
+}
+
+// Steps over the value of a tag of a known type, like read_tag_known but without building it
+fn skip_tag_known<'a>(input: &'a [u8], tag_type: u8, options: &ReadOptions, depth: usize) -> IResult<&'a [u8], ()> {
+    if depth > options.max_depth {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(1))));
+    }
+
+    let skip_len = |elem_len: usize| -> IResult<&'a [u8], ()> {
+        let (rest, len) = read_len(input, tag_type, options)?;
+        let (rest, ()) = require_elements(rest, tag_type, len, elem_len)?;
+        Ok((&rest[len * elem_len..], ()))
+    };
+
+    let result = match TagType::from_u8(tag_type) {
+        Some(TagType::Byte) => take!(input, 1).map(|(rest, _)| (rest, ())),
+        Some(TagType::Short) => take!(input, 2).map(|(rest, _)| (rest, ())),
+        Some(TagType::Int) | Some(TagType::Float) => take!(input, 4).map(|(rest, _)| (rest, ())),
+        Some(TagType::Long) | Some(TagType::Double) => take!(input, 8).map(|(rest, _)| (rest, ())),
+        Some(TagType::ByteArray) => skip_len(1),
+        Some(TagType::String) => u16!(input, options.endianness).and_then(|(rest, len)| take!(rest, len)).map(|(rest, _)| (rest, ())),
+        Some(TagType::List) => skip_tag_list(input, options, depth),
+        Some(TagType::Compound) => skip_tag_compound(input, options, depth),
+        Some(TagType::IntArray) => skip_len(4),
+        Some(TagType::LongArray) => skip_len(8),
+        Some(TagType::End) | None => return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(0)))),
+    };
+
+    with_tag_context(input, tag_type, result)
+}
+
+fn skip_tag_list<'a>(input: &'a [u8], options: &ReadOptions, depth: usize) -> IResult<&'a [u8], ()> {
+    let (rest, elem_type) = be_u8(input)?;
+    let (mut rest, len) = read_len(rest, 9, options)?;
+
+    for _ in 0..len {
+        rest = skip_tag_known(rest, elem_type, options, depth + 1)?.0;
+    }
+
+    Ok((rest, ()))
+}
+
+fn skip_tag_compound<'a>(input: &'a [u8], options: &ReadOptions, depth: usize) -> IResult<&'a [u8], ()> {
+    let mut input = input;
+
+    loop {
+        let (rest, tag_type) = be_u8(input)?;
+
+        if tag_type == 0 {
+            return Ok((rest, ()));
+        }
+
+        let (rest, len) = u16!(rest, options.endianness)?;
+        let (rest, _) = take!(rest, len)?;
+        input = skip_tag_known(rest, tag_type, options, depth + 1)?.0;
+    }
+}
 
 // Reads tag of which the type is already known
-fn read_tag_known(input: &[u8], tag_type: u8) -> IResult<&[u8], NBTTag> {
+fn read_tag_known<'a>(input: &'a [u8], tag_type: u8, options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTag> {
+    if depth > options.max_depth {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(1))));
+    }
+
+    let result = match TagType::from_u8(tag_type) {
+        Some(TagType::Byte) => read_tag_byte(input),
+        Some(TagType::Short) => read_tag_short(input, options.endianness),
+        Some(TagType::Int) => read_tag_int(input, options.endianness),
+        Some(TagType::Long) => read_tag_long(input, options.endianness),
+        Some(TagType::Float) => read_tag_float(input, options.endianness),
+        Some(TagType::Double) => read_tag_double(input, options.endianness),
+        Some(TagType::ByteArray) => read_tag_byte_array(input, options),
+        Some(TagType::String) => read_tag_string(input, options),
+        Some(TagType::List) => read_tag_list(input, options, depth),
+        Some(TagType::Compound) => read_tag_compound(input, options, depth),
+        Some(TagType::IntArray) => read_tag_int_array(input, options),
+        Some(TagType::LongArray) => read_tag_long_array(input, options),
+        Some(TagType::End) | None => return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(0)))),
+    };
+
+    with_tag_context(input, tag_type, result)
+}
+
+// Turns an error of the parser of a tag into a failure that remembers the type of the tag. Failures
+// are passed on untouched so the innermost tag wins, and count! does not replace them with an
+// error of its own.
+fn with_tag_context<'a, O>(input: &'a [u8], tag_type: u8, result: IResult<&'a [u8], O>) -> IResult<&'a [u8], O> {
+    let kind = ErrorKind::Custom(TAG_CONTEXT + u32::from(tag_type));
+
+    match result {
+        Err(nom::Err::Error(nom::Context::Code(position, _))) => Err(nom::Err::Failure(error_position!(position, kind))),
+        Err(nom::Err::Incomplete(_)) => {
+            let kind = ErrorKind::Custom(INCOMPLETE_CONTEXT + u32::from(tag_type));
+            Err(nom::Err::Failure(error_position!(&input[input.len()..], kind)))
+        }
+        result => result,
+    }
+}
+
+// Converts a nom error into an NBTError with the offset of the failure from the start of bytes.
+// options have to be the ones bytes were read with.
+pub(crate) fn parse_error(bytes: &[u8], err: nom::Err<&[u8]>, options: &ReadOptions) -> NBTError {
+    let (position, kind) = match err {
+        nom::Err::Error(nom::Context::Code(position, kind)) |
+        nom::Err::Failure(nom::Context::Code(position, kind)) => (position, kind),
+        nom::Err::Incomplete(_) => return NBTError::Parse { offset: bytes.len(), tag: None },
+    };
+
+    let tag = match kind {
+        ErrorKind::Custom(DUPLICATE_KEY) => {
+            let name = read_name(&position[1..], options).map(|(_, name)| name.into_owned()).unwrap_or_default();
+            return NBTError::DuplicateKey(name);
+        }
+        ErrorKind::Custom(code) if code >= INCOMPLETE_CONTEXT => Some((code - INCOMPLETE_CONTEXT) as u8),
+        ErrorKind::Custom(code) if code >= EOF_CONTEXT => {
+            // The length was read right before the elements
+            let offset = bytes.len() - position.len();
+            let expected = offset.checked_sub(4)
+                .and_then(|start| i32!(&bytes[start..], options.endianness).ok())
+                .map_or(0, |(_, len)| len as usize);
+            let size = match code - EOF_CONTEXT {
+                7 => 1,
+                11 => 4,
+                _ => 8,
+            };
+
+            return NBTError::UnexpectedEof { kind: len_kind(code - EOF_CONTEXT), read: position.len() / size, expected };
+        }
+        ErrorKind::Custom(code) if code >= NEGATIVE_CONTEXT => {
+            let len = i32!(position, options.endianness).map_or(0, |(_, len)| len);
+
+            return NBTError::NegativeLength { kind: len_kind(code - NEGATIVE_CONTEXT), len };
+        }
+        ErrorKind::Custom(code) if code >= LIMIT_CONTEXT => {
+            let len = i32!(position, options.endianness).map_or(0, |(_, len)| len as usize);
+
+            return NBTError::TooLong { kind: len_kind(code - LIMIT_CONTEXT), len, max: options.max_len };
+        }
+        ErrorKind::Custom(code) if code >= TAG_CONTEXT => Some((code - TAG_CONTEXT) as u8),
+        _ => None,
+    };
+
+    NBTError::Parse { offset: bytes.len() - position.len(), tag }
+}
+
+// Whether err was raised because the input ends before the tag being read does
+fn is_incomplete(err: &nom::Err<&[u8]>) -> bool {
+    match *err {
+        nom::Err::Incomplete(_) => true,
+        nom::Err::Error(nom::Context::Code(_, ErrorKind::Custom(code))) |
+        nom::Err::Failure(nom::Context::Code(_, ErrorKind::Custom(code))) => code >= EOF_CONTEXT,
+        _ => false,
+    }
+}
+
+// The name of the array or list type whose length was rejected by read_len
+fn len_kind(tag_type: u32) -> &'static str {
     match tag_type {
-        1 => read_tag_byte(input),
-        2 => read_tag_short(input),
-        3 => read_tag_int(input),
-        4 => read_tag_long(input),
-        5 => read_tag_float(input),
-        6 => read_tag_double(input),
-        7 => read_tag_byte_array(input),
-        8 => read_tag_string(input),
-        9 => read_tag_list(input),
-        10 => read_tag_compound(input),
-        11 => read_tag_int_array(input),
-        12 => read_tag_long_array(input),
-        _ => Err(nom::Err::Error(error_position!(input, ErrorKind::Custom(0)))),
-    }
-}
-
-fn file_from_tuple(tuple: (&str, NBTTag)) -> Option<NBTFile> {
-    if let &NBTTag::TagCompound(_) = &tuple.1 {
-        Some(NBTFile {
-            root_name: tuple.0.clone().to_owned(),
+        7 => "TagByteArray",
+        9 => "TagList",
+        11 => "TagIntArray",
+        _ => "TagLongArray",
+    }
+}
+
+fn read_tag_list_ref<'a>(input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTagRef<'a>> {
+    do_parse!(input,
+        elems_type: be_u8 >>
+        len: apply!(read_len, 9, options) >>
+        apply!(charge, options, len.saturating_mul(mem::size_of::<NBTTagRef>())) >>
+        elems: count!(apply!(read_tag_known_ref, elems_type, options, depth + 1), len) >>
+        (NBTTagRef::TagList(elems))
+    )
+}
+
+fn read_tag_compound_ref<'a>(input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTagRef<'a>> {
+    do_parse!(input,
+        elems: many_till!(apply!(read_tag_ref, options, depth + 1), tag!([0x00])) >>
+        (NBTTagRef::TagCompound(elems.0))
+    )
+}
+
+fn read_tag_ref<'a>(input: &'a [u8], options: &ReadState, depth: usize) -> IResult<&'a [u8], (Cow<'a, str>, NBTTagRef<'a>)> {
+    do_parse!(input,
+        tag_type: be_u8                                              >>
+        name: apply!(read_name, options)                             >>
+        output: apply!(read_tag_known_ref, tag_type, options, depth) >>
+        (name, output)
+    )
+}
+
+// Borrowing counterpart of read_tag_known. Only strings, byte arrays, lists and compounds need
+// their own parsers; all other values are read by the owned parsers and converted.
+fn read_tag_known_ref<'a>(input: &'a [u8], tag_type: u8, options: &ReadState, depth: usize) -> IResult<&'a [u8], NBTTagRef<'a>> {
+    if depth > options.max_depth {
+        return Err(nom::Err::Failure(error_position!(input, ErrorKind::Custom(1))));
+    }
+
+    let result = match tag_type {
+        7 => do_parse!(input,
+            len: apply!(read_len, 7, options)    >>
+            apply!(require_elements, 7, len, 1) >>
+            val: take!(len)                      >>
+            (NBTTagRef::TagByteArray(val))
+        ),
+        8 => do_parse!(input,
+            val: apply!(read_name, options) >>
+            (NBTTagRef::TagString(val))
+        ),
+        9 => read_tag_list_ref(input, options, depth),
+        10 => read_tag_compound_ref(input, options, depth),
+        _ => {
+            let (rest, tag) = read_tag_known(input, tag_type, options, depth)?;
+
+            let tag_ref = match tag {
+                NBTTag::TagByte(value) => NBTTagRef::TagByte(value),
+                NBTTag::TagShort(value) => NBTTagRef::TagShort(value),
+                NBTTag::TagInt(value) => NBTTagRef::TagInt(value),
+                NBTTag::TagLong(value) => NBTTagRef::TagLong(value),
+                NBTTag::TagFloat(value) => NBTTagRef::TagFloat(value),
+                NBTTag::TagDouble(value) => NBTTagRef::TagDouble(value),
+                NBTTag::TagIntArray(value) => NBTTagRef::TagIntArray(value),
+                NBTTag::TagLongArray(value) => NBTTagRef::TagLongArray(value),
+                NBTTag::TagEnd | NBTTag::TagByteArray(_) | NBTTag::TagString(_) | NBTTag::TagList(_) | NBTTag::TagCompound(_) => {
+                    unreachable!("read_tag_known fails on TagEnd, and the other types are read above")
+                }
+            };
+
+            Ok((rest, tag_ref))
+        }
+    };
+
+    with_tag_context(input, tag_type, result)
+}
+
+// Reads a file without copying names, strings and byte arrays out of bytes, returning the name and
+// value of the root compound. Names and strings are only copied when they have to be decoded from
+// modified UTF-8. options.compression and options.on_duplicate_key are ignored: bytes have to be
+// uncompressed, and compounds keep every entry they were read with.
+pub fn read_nbt_ref<'a>(bytes: &'a [u8], options: &ReadOptions) -> Result<(Cow<'a, str>, NBTTagRef<'a>), NBTError> {
+    match bytes.first() {
+        Some(&found) if found != 10 && !options.allow_non_compound_root => return Err(NBTError::InvalidRoot { found }),
+        _ => (),
+    }
+
+    let state = ReadState::new(options);
+
+    match read_tag_ref(bytes, &state, 0) {
+        Ok((rest, _)) if options.reject_trailing && !rest.is_empty() => Err(NBTError::TrailingBytes(rest.len())),
+        Ok((_, (name, root @ NBTTagRef::TagCompound(_)))) => Ok((name, root)),
+        Ok((_, (name, root))) if options.allow_non_compound_root => Ok((name, root)),
+        Ok(_) => Err(NBTError::InvalidRoot { found: bytes[0] }),
+        Err(err) => Err(state.error(bytes, err)),
+    }
+}
+
+// Reads one file preceded by its length as a big endian u32, as stored by some save containers,
+// returning the file and the bytes following it
+pub fn read_sized_nbt(bytes: &[u8]) -> Result<(NBTFile, &[u8]), NBTError> {
+    if bytes.len() < 4 {
+        return Err(NBTError::Parse { offset: bytes.len(), tag: None });
+    }
+
+    let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+
+    if bytes.len() - 4 < len {
+        return Err(NBTError::Parse { offset: bytes.len(), tag: None });
+    }
+
+    // The file is required to take up exactly len bytes, so padding inside the record is an error
+    let file = NBTFile::read(&bytes[4..4 + len], &ReadOptions { reject_trailing: true, ..ReadOptions::default() })?;
+
+    Ok((file, &bytes[4 + len..]))
+}
+
+// Iterates over the files of a stream of length-prefixed files, see read_sized_nbt. Iteration ends
+// after the bytes are exhausted or the first error.
+pub struct NbtStream<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> NbtStream<'a> {
+    pub fn new(bytes: &'a [u8]) -> NbtStream<'a> {
+        NbtStream { bytes }
+    }
+}
+
+impl<'a> Iterator for NbtStream<'a> {
+    type Item = Result<NBTFile, NBTError>;
+
+    fn next(&mut self) -> Option<Result<NBTFile, NBTError>> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        match read_sized_nbt(self.bytes) {
+            Ok((file, rest)) => {
+                self.bytes = rest;
+                Some(Ok(file))
+            }
+            Err(err) => {
+                self.bytes = &[];
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// Reads uncompressed files from bytes that arrive in pieces, like from a socket. The bytes fed so
+// far are buffered and read again on every feed until they hold a complete file, so this suits
+// small files. Bytes following a file are kept for the next one; feed an empty slice to read a
+// file that is already buffered completely.
+pub struct IncrementalReader {
+    buffer: Vec<u8>,
+    options: ReadOptions,
+}
+
+impl IncrementalReader {
+    // Compressed streams can't be read in pieces, so options.compression is required to be
+    // Some(Compression::None); detection with None is rejected as well, as a compressed file
+    // would only fail once it was read completely
+    pub fn new(options: ReadOptions) -> Result<IncrementalReader, NBTError> {
+        if options.compression != Some(Compression::None) {
+            return Err(NBTError::InvalidValue(format!(
+                "IncrementalReader only reads uncompressed files, but the compression is {:?}", options.compression)));
+        }
+
+        Ok(IncrementalReader { buffer: Vec::new(), options })
+    }
+
+    // Appends bytes to the buffer and reads a file from it, which is Pending while the buffer ends
+    // before the file does. After an error, the buffered bytes are discarded.
+    pub fn feed(&mut self, bytes: &[u8]) -> Poll<Result<NBTFile, NBTError>> {
+        self.buffer.extend_from_slice(bytes);
+
+        let result = match self.buffer.first() {
+            None => return Poll::Pending,
+            Some(&found) if found != 10 && !self.options.allow_non_compound_root => Err(NBTError::InvalidRoot { found }),
+            Some(&found) => {
+                let state = ReadState::new(&self.options);
+
+                match read_tag(&self.buffer, &state, 0) {
+                    Ok((rest, root)) => match file_from_tuple(root, &self.options) {
+                        Some(file) => Ok((file.into_owned(), self.buffer.len() - rest.len())),
+                        None => Err(NBTError::InvalidRoot { found }),
+                    },
+                    // Only input that ends inside the file means that more of it has to arrive
+                    Err(ref err) if is_incomplete(err) => return Poll::Pending,
+                    Err(err) => Err(state.error(&self.buffer, err)),
+                }
+            }
+        };
+
+        match result {
+            Ok((file, len)) => {
+                self.buffer.drain(..len);
+                Poll::Ready(Ok(file))
+            }
+            Err(err) => {
+                self.buffer.clear();
+                Poll::Ready(Err(err))
+            }
+        }
+    }
+
+    // Number of bytes that were fed but not yet read as part of a file
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+fn file_from_tuple<'a>(tuple: (Cow<'a, str>, NBTTag), options: &ReadOptions) -> Option<NBTFileRef<'a>> {
+    if options.allow_non_compound_root || matches!(tuple.1, NBTTag::TagCompound(_)) {
+        Some(NBTFileRef {
+            root_name: tuple.0,
             root: tuple.1,
+            compression: Compression::None,
         })
     } else {
         None
     }
 }
 
-fn tuple_vector_to_hash_map(input: Vec<(&str, NBTTag)>) -> HashMap<String, NBTTag> {
-    let mut map = HashMap::new();
+#[test]
+fn test_incremental_reader() {
+    use file::NBTFileBuilder;
 
-    for item in input.iter() {
-        map.insert(item.0.clone().to_owned(), item.1.clone());
+    let file = NBTFileBuilder::new().name("Data").put("name", "Steve").put("Data", vec![1i32, 2, 3]).build();
+    let bytes = file.as_bytes().unwrap();
+
+    let options = ReadOptions { compression: Some(Compression::None), ..ReadOptions::default() };
+    let mut reader = IncrementalReader::new(options).unwrap();
+    for &byte in &bytes[..bytes.len() - 1] {
+        assert!(reader.feed(&[byte]).is_pending());
     }
 
-    return map;
-}
+    // The last byte completes the first file and the rest of the piece starts a second one
+    let mut piece = vec![bytes[bytes.len() - 1]];
+    piece.extend_from_slice(&bytes[..5]);
+    assert!(matches!(reader.feed(&piece), Poll::Ready(Ok(ref read)) if *read == file));
+    assert_eq!(reader.buffered(), 5);
 
-#[test]
-fn test_tuple_vec_to_hash_map() {
-    let input = vec![
-        ("Hello World!", NBTTag::TagString("Test".to_owned())),
-        ("Bye World!", NBTTag::TagInt(3))
-    ];
+    assert!(reader.feed(&bytes[5..]).is_ready());
+    assert!(reader.feed(&[]).is_pending());
 
-    let mut expected = HashMap::new();
+    assert!(matches!(reader.feed(&[0x08, 0x00]), Poll::Ready(Err(NBTError::InvalidRoot { found: 8 }))));
+    assert_eq!(reader.buffered(), 0);
 
-    expected.insert("Hello World!".to_owned(), NBTTag::TagString("Test".to_owned()));
-    expected.insert("Bye World!".to_owned(), NBTTag::TagInt(3));
+    // An entry of an unknown type that ends the bytes fed is an error, not the start of more input
+    assert!(matches!(reader.feed(&[0x0A, 0x00, 0x00, 0x0D, 0x00, 0x00]), Poll::Ready(Err(NBTError::Parse { offset: 6, .. }))));
+    assert_eq!(reader.buffered(), 0);
 
-    assert_eq!(tuple_vector_to_hash_map(input), expected);
+    assert!(IncrementalReader::new(ReadOptions::default()).is_err());
+    assert!(IncrementalReader::new(ReadOptions { compression: Some(Compression::Gzip), ..ReadOptions::default() }).is_err());
 }
 
 #[test]
 fn test_read_name() {
-    assert_eq!(read_tag_name(vec![0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6F].as_slice()), Ok((&b""[..], "Hello")))
+    let input = [0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6F];
+    assert_eq!(read_name(&input, &ReadOptions::default()), Ok((&b""[..], Cow::Borrowed("Hello"))))
 }
 
 #[test]
@@ -201,12 +924,412 @@ fn test_read_nbt_file() {
         0x0A, 0x00, 0x01, 0x65, 0x08, 0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6f, 0x00
     ];
 
-    let mut compound_contents = HashMap::new();
+    let mut compound_contents = IndexMap::new();
     compound_contents.insert("Hello".to_owned(), NBTTag::TagString("Hello".to_owned()));
 
-    assert_eq!(read_nbt_file(input.as_slice()), Ok((&b""[..],
-                                                    Some(NBTFile {
-                                                        root_name: "e".to_owned(),
+    assert_eq!(read_nbt_file(input.as_slice(), &ReadOptions::default()).unwrap(), (&b""[..],
+                                                    Some(NBTFileRef {
+                                                        root_name: Cow::Borrowed("e"),
                                                         root: NBTTag::TagCompound(compound_contents),
-                                                    }))));
+                                                        compression: Compression::None,
+                                                    })));
+}
+
+#[test]
+fn test_read_headless_nbt() {
+    let input = vec![0x0A, 0x08, 0x00, 0x01, 0x61, 0x00, 0x01, 0x62, 0x00];
+
+    let mut compound_contents = IndexMap::new();
+    compound_contents.insert("a".to_owned(), NBTTag::TagString("b".to_owned()));
+
+    assert_eq!(read_headless_nbt(input.as_slice()).unwrap(), NBTTag::TagCompound(compound_contents));
+    assert!(read_headless_nbt(&[0x0A, 0x08]).is_err());
+}
+
+#[test]
+fn test_read_rejects_invalid_utf8() {
+    assert!(read_name(&[0x00, 0x02, 0xC3, 0x28], &ReadOptions::default()).is_err());
+    assert!(read_nbt_file(&[0x0A, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0xFF, 0x00], &ReadOptions::default()).is_err());
+}
+
+#[test]
+fn test_read_rejects_deep_nesting() {
+    // A root compound holding a list of lists nested far deeper than MAX_DEPTH
+    let mut input = vec![0x0A, 0x00, 0x00, 0x09, 0x00, 0x00];
+
+    for _ in 0..100_000 {
+        input.extend_from_slice(&[0x09, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    assert!(read_nbt_file(&input, &ReadOptions::default()).is_err());
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn test_read_arbitrary_bytes_never_panics(input in proptest::collection::vec(proptest::num::u8::ANY, 0..256)) {
+        let _ = read_nbt_file(&input, &ReadOptions::default());
+        let _ = read_headless_nbt(&input);
+    }
+
+    #[test]
+    fn test_read_mutated_file_never_panics(index in 0usize..20, byte in proptest::num::u8::ANY) {
+        let mut input = vec![
+            0x0A, 0x00, 0x01, 0x65, 0x09, 0x00, 0x01, 0x6C, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x07, 0x00,
+            0x00, 0x00
+        ];
+        input[index] = byte;
+
+        let _ = read_nbt_file(&input, &ReadOptions::default());
+    }
+}
+
+#[test]
+fn test_read_nbt_ref() {
+    let input = vec![
+        0x0A, 0x00, 0x01, 0x65, 0x08, 0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x00, 0x05, 0x48, 0x65, 0x6C, 0x6C,
+        0x6f, 0x07, 0x00, 0x01, 0x62, 0x00, 0x00, 0x00, 0x02, 0x01, 0xFF, 0x03, 0x00, 0x01, 0x69, 0x00, 0x00, 0x00,
+        0x07, 0x00
+    ];
+
+    let (name, root) = read_nbt_ref(&input, &ReadOptions::default()).unwrap();
+
+    assert_eq!(name, "e");
+    assert_eq!(root.get("Hello"), Some(&NBTTagRef::TagString(Cow::Borrowed("Hello"))));
+    assert_eq!(root.get("b"), Some(&NBTTagRef::TagByteArray(&[0x01, 0xFF])));
+    assert_eq!(root.get("i"), Some(&NBTTagRef::TagInt(7)));
+    assert_eq!(root.to_owned(), read_nbt_file(&input, &ReadOptions::default()).unwrap().1.unwrap().root);
+    assert!(read_nbt_ref(&input[..10], &ReadOptions::default()).is_err());
+
+    // The limits of the options apply like they do to the owned reader
+    let short = ReadOptions { max_len: 1, ..ReadOptions::default() };
+    assert!(matches!(read_nbt_ref(&input, &short), Err(NBTError::TooLong { kind: "TagByteArray", len: 2, max: 1 })));
+
+    let mut trailing = input.clone();
+    trailing.push(0x00);
+    let strict = ReadOptions { reject_trailing: true, ..ReadOptions::default() };
+    assert!(matches!(read_nbt_ref(&trailing, &strict), Err(NBTError::TrailingBytes(1))));
+
+    let little = ReadOptions { endianness: Endianness::Little, ..ReadOptions::default() };
+    assert!(read_nbt_ref(&input, &little).is_err());
+}
+
+#[cfg(test)]
+fn read_error(input: &[u8], options: &ReadOptions) -> NBTError {
+    read_nbt_file(input, options).unwrap_err()
+}
+
+#[test]
+fn test_read_error_offset() {
+    // The string value claims 5 bytes but only 3 follow
+    let input = vec![0x0A, 0x00, 0x00, 0x08, 0x00, 0x01, 0x61, 0x00, 0x05, 0x48, 0x65, 0x6C];
+
+    match read_error(&input, &ReadOptions::default()) {
+        NBTError::Parse { offset, tag } => {
+            assert_eq!(offset, 12);
+            assert_eq!(tag, Some(8));
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+
+    // An int followed by a sibling of the unknown tag type 13
+    let input = vec![0x0A, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0D, 0x00, 0x00, 0x00];
+
+    assert_eq!(read_error(&input, &ReadOptions::default()).to_string(), "Parse failed at byte 13");
+    assert_eq!(read_headless_nbt(&[0x09, 0x03, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00]).unwrap_err().to_string(),
+               "Parse failed at byte 11 while reading TAG_Int");
+}
+
+#[test]
+fn test_read_duplicate_keys() {
+    // A compound containing the byte "a" twice, first with value 1 and then with value 2
+    let input = vec![0x0A, 0x00, 0x00, 0x01, 0x00, 0x01, 0x61, 0x01, 0x01, 0x00, 0x01, 0x61, 0x02, 0x00];
+
+    let read = |on_duplicate_key| {
+        read_nbt_file(&input, &ReadOptions { on_duplicate_key, ..ReadOptions::default() }).unwrap().1.unwrap().root
+    };
+
+    assert_eq!(read(DuplicateKey::KeepLast).get("a"), Some(&NBTTag::TagByte(2)));
+    assert_eq!(read(DuplicateKey::KeepFirst).get("a"), Some(&NBTTag::TagByte(1)));
+
+    let options = ReadOptions { on_duplicate_key: DuplicateKey::Error, ..ReadOptions::default() };
+    assert_eq!(read_error(&input, &options).to_string(), "Compound contains the key \"a\" more than once");
+}
+
+#[test]
+fn test_read_options() {
+    // A compound holding the int array "a" of two elements, written in little endian
+    let input = vec![
+        0x0A, 0x00, 0x00, 0x0B, 0x01, 0x00, 0x61, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+        0x00, 0x00
+    ];
+
+    let little = ReadOptions { endianness: Endianness::Little, ..ReadOptions::default() };
+    let file = read_nbt_file(&input, &little).unwrap().1.unwrap();
+    assert_eq!(file.root.get("a"), Some(&NBTTag::TagIntArray(vec![1, 2])));
+
+    let limited = ReadOptions { max_len: 1, ..little.clone() };
+    assert_eq!(read_error(&input, &limited).to_string(), "Length 2 of TagIntArray exceeds the maximum of 1");
+
+    let shallow = ReadOptions { max_depth: 0, ..little };
+    assert!(read_nbt_file(&input, &shallow).is_err());
+}
+
+#[test]
+fn test_read_truncated_list() {
+    // A compound holding the list "l" of three ints, cut off after the second
+    let input = vec![0x0A, 0x00, 0x00, 0x09, 0x00, 0x01, 0x6C, 0x03, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02];
+
+    match read_error(&input, &ReadOptions::default()) {
+        NBTError::Parse { offset, tag } => assert_eq!((offset, tag), (input.len(), Some(3))),
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(read_nbt_ref(&input, &ReadOptions::default()).is_err());
+    assert!(read_root_field(&input, "l", &ReadOptions::default()).is_err());
+}
+
+#[test]
+fn test_read_truncated_arrays() {
+    // Each array declares three elements, but only holds two and a half
+    let cases: &[(u8, &[u8], &str)] = &[
+        (7, &[0x01, 0x02], "Input ends after 2 of the 3 elements of TagByteArray"),
+        (11, &[0, 0, 0, 1, 0, 0, 0, 2, 0, 0], "Input ends after 2 of the 3 elements of TagIntArray"),
+        (12, &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0], "Input ends after 2 of the 3 elements of TagLongArray"),
+    ];
+
+    for &(tag_type, elements, msg) in cases {
+        let mut input = vec![0x0A, 0x00, 0x00, tag_type, 0x00, 0x01, 0x61, 0x00, 0x00, 0x00, 0x03];
+        input.extend_from_slice(elements);
+
+        assert_eq!(read_error(&input, &ReadOptions::default()).to_string(), msg);
+        assert_eq!(read_nbt_ref(&input, &ReadOptions::default()).unwrap_err().to_string(), msg);
+        assert_eq!(skip_value(&input[7..], tag_type, Endianness::Big).unwrap_err().to_string(), msg);
+    }
+}
+
+#[test]
+fn test_read_empty_arrays() {
+    use write::encode_value;
+
+    for tag in &[NBTTag::TagByteArray(Vec::new()), NBTTag::TagIntArray(Vec::new()), NBTTag::TagLongArray(Vec::new())] {
+        let bytes = encode_value(tag, Endianness::Big).unwrap();
+        assert_eq!(decode_value(&bytes, u8::from(tag.tag_type()), Endianness::Big).unwrap(), (tag.clone(), 4));
+    }
+}
+
+#[test]
+fn test_read_negative_length() {
+    // A compound holding the byte array "a" of length -1, then the same with a list of ints
+    let array = vec![0x0A, 0x00, 0x00, 0x07, 0x00, 0x01, 0x61, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00];
+    let list = vec![0x0A, 0x00, 0x00, 0x09, 0x00, 0x01, 0x61, 0x03, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+    assert_eq!(read_error(&array, &ReadOptions::default()).to_string(), "Length -1 of TagByteArray is negative");
+    assert_eq!(read_error(&list, &ReadOptions::default()).to_string(), "Length -2147483648 of TagList is negative");
+    assert!(read_nbt_ref(&array, &ReadOptions::default()).is_err());
+    assert!(read_nbt_ref(&list, &ReadOptions::default()).is_err());
+    assert!(skip_value(&[0xFF, 0xFF, 0xFF, 0xFF], 11, Endianness::Big).is_err());
+}
+
+#[test]
+fn test_read_max_total_bytes() {
+    // Ten int arrays of 16 bytes each, none of which is long by itself
+    let mut map = IndexMap::new();
+    for i in 0..10 {
+        map.insert(i.to_string(), NBTTag::TagIntArray(vec![1, 2, 3, 4]));
+    }
+    let input = NBTFile::new(String::new(), Some(NBTTag::TagCompound(map))).as_bytes().unwrap();
+
+    let roomy = ReadOptions { max_total_bytes: 170, ..ReadOptions::default() };
+    assert!(read_nbt_file(&input, &roomy).is_ok());
+
+    let tight = ReadOptions { max_total_bytes: 100, ..ReadOptions::default() };
+    match read_error(&input, &tight) {
+        NBTError::BudgetExceeded { total, max } => assert_eq!((total, max), (101, 100)),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn test_read_mutf8() {
+    // The name "a\0" and the string value U+1F600, both in modified UTF-8
+    let input = vec![
+        0x0A, 0x00, 0x00, 0x08, 0x00, 0x03, 0x61, 0xC0, 0x80, 0x00, 0x06, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0x00
+    ];
+
+    let file = read_nbt_file(&input, &ReadOptions::default()).unwrap().1.unwrap();
+    assert_eq!(file.root.get("a\0"), Some(&NBTTag::TagString("\u{1F600}".to_owned())));
+
+    let strict = ReadOptions { string_encoding: StringEncoding::Utf8, ..ReadOptions::default() };
+    assert!(read_nbt_file(&input, &strict).is_err());
+
+    // The borrowing reader decodes the same way
+    let (_, root) = read_nbt_ref(&input, &ReadOptions::default()).unwrap();
+    assert_eq!(root.get("a\0"), Some(&NBTTagRef::TagString(Cow::Borrowed("\u{1F600}"))));
+    assert_eq!(root.to_owned(), file.root);
+}
+
+#[test]
+fn test_read_non_compound_root() {
+    // A file with a list of two bytes named "l" as its root
+    let input = vec![0x09, 0x00, 0x01, 0x6C, 0x01, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02];
+
+    assert_eq!(read_nbt_file(&input, &ReadOptions::default()).unwrap().1, None);
+
+    let options = ReadOptions { allow_non_compound_root: true, ..ReadOptions::default() };
+    let file = read_nbt_file(&input, &options).unwrap().1.unwrap();
+
+    assert_eq!(file.root_name, "l");
+    assert_eq!(file.root, NBTTag::TagList(vec![NBTTag::TagByte(1), NBTTag::TagByte(2)]));
+}
+
+#[test]
+fn test_read_sized_nbt() {
+    let first = vec![0x0A, 0x00, 0x01, 0x61, 0x00];
+    let second = vec![0x0A, 0x00, 0x01, 0x62, 0x01, 0x00, 0x01, 0x63, 0x07, 0x00];
+
+    let mut input = Vec::new();
+    for file in &[&first, &second] {
+        input.extend_from_slice(&(file.len() as u32).to_be_bytes());
+        input.extend_from_slice(file);
+    }
+
+    let (file, rest) = read_sized_nbt(&input).unwrap();
+    assert_eq!(file.root_name, "a");
+    assert_eq!(rest.len(), 4 + second.len());
+
+    let names: Vec<String> = NbtStream::new(&input).map(|file| file.unwrap().root_name).collect();
+    assert_eq!(names, vec!["a", "b"]);
+
+    let results: Vec<_> = NbtStream::new(&input[..input.len() - 1]).collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[1].is_err());
+
+    let mut padded = ((first.len() + 2) as u32).to_be_bytes().to_vec();
+    padded.extend_from_slice(&first);
+    padded.extend_from_slice(&[0x00, 0x00]);
+    assert!(matches!(read_sized_nbt(&padded), Err(NBTError::TrailingBytes(2))));
+}
+
+#[test]
+fn test_read_bedrock_records() {
+    use write::{write_tag_with_options, WriteOptions};
+
+    let mut chest = IndexMap::new();
+    chest.insert("id".to_owned(), NBTTag::TagString("Chest".to_owned()));
+    let mut sign = IndexMap::new();
+    sign.insert("id".to_owned(), NBTTag::TagString("Sign".to_owned()));
+    let records = vec![NBTTag::TagCompound(chest), NBTTag::TagCompound(sign)];
+
+    let mut bytes = Vec::new();
+    for record in &records {
+        let options = WriteOptions { endianness: Endianness::Little, ..WriteOptions::default() };
+        bytes.extend(write_tag_with_options(record, true, true, Some(""), &options).unwrap());
+    }
+
+    assert_eq!(read_bedrock_records(&bytes).unwrap(), records);
+    assert_eq!(read_bedrock_records(&[]).unwrap(), vec![]);
+    assert_eq!(read_bedrock_records(&bytes[..bytes.len() - 3]).unwrap_err().to_string(),
+               format!("Parse failed at byte {} while reading TAG_String", bytes.len() - 3));
+}
+
+#[test]
+fn test_read_root_field() {
+    use file::NBTFileBuilder;
+    use write::WriteOptions;
+
+    let file = NBTFileBuilder::new()
+        .put("Data", NBTTag::TagList(vec![NBTTag::TagCompound(IndexMap::new()), NBTTag::TagCompound(IndexMap::new())]))
+        .put("Name", "World")
+        .put("Heights", NBTTag::TagLongArray(vec![1, 2]))
+        .put("Names", NBTTag::TagList(vec![NBTTag::TagString("a".to_owned())]))
+        .put("DataVersion", 3465)
+        .build();
+    let bytes = file.as_bytes().unwrap();
+
+    assert_eq!(read_root_field(&bytes, "DataVersion", &ReadOptions::default()).unwrap(), Some(NBTTag::TagInt(3465)));
+    assert_eq!(read_root_field(&bytes, "Name", &ReadOptions::default()).unwrap(), Some(NBTTag::TagString("World".to_owned())));
+    assert_eq!(read_root_field(&bytes, "Missing", &ReadOptions::default()).unwrap(), None);
+    assert!(read_root_field(&bytes[..bytes.len() - 8], "DataVersion", &ReadOptions::default()).is_err());
+    assert!(read_root_field(&bytes[..bytes.len() - 1], "Missing", &ReadOptions::default()).is_err());
+
+    let little = ReadOptions { endianness: Endianness::Little, ..ReadOptions::default() };
+    let bytes = file.to_bytes(&WriteOptions { endianness: Endianness::Little, ..WriteOptions::default() }).unwrap();
+    assert_eq!(read_root_field(&bytes, "DataVersion", &little).unwrap(), Some(NBTTag::TagInt(3465)));
+}
+
+#[test]
+fn test_read_root_field_duplicate_keys() {
+    // The same compound as in test_read_duplicate_keys, containing the byte "a" twice
+    let input = vec![0x0A, 0x00, 0x00, 0x01, 0x00, 0x01, 0x61, 0x01, 0x01, 0x00, 0x01, 0x61, 0x02, 0x00];
+
+    let read = |on_duplicate_key| read_root_field(&input, "a", &ReadOptions { on_duplicate_key, ..ReadOptions::default() });
+
+    for &policy in &[DuplicateKey::KeepLast, DuplicateKey::KeepFirst] {
+        let full = read_nbt_file(&input, &ReadOptions { on_duplicate_key: policy, ..ReadOptions::default() }).unwrap().1.unwrap();
+        assert_eq!(read(policy).unwrap().as_ref(), full.root.get("a"));
+    }
+
+    assert_eq!(read(DuplicateKey::KeepLast).unwrap(), Some(NBTTag::TagByte(2)));
+    assert_eq!(read(DuplicateKey::Error).unwrap_err().to_string(), "Compound contains the key \"a\" more than once");
+}
+
+#[test]
+fn test_decode_value() {
+    use write::encode_value;
+
+    let mut compound = IndexMap::new();
+    compound.insert("a".to_owned(), NBTTag::TagShort(1));
+
+    let tags = vec![
+        NBTTag::TagByte(-1),
+        NBTTag::TagShort(2),
+        NBTTag::TagInt(3),
+        NBTTag::TagLong(4),
+        NBTTag::TagFloat(5.5),
+        NBTTag::TagDouble(6.5),
+        NBTTag::TagByteArray(vec![7, 8]),
+        NBTTag::TagString("nine".to_owned()),
+        NBTTag::TagList(vec![NBTTag::TagInt(10)]),
+        NBTTag::TagCompound(compound),
+        NBTTag::TagIntArray(vec![11, 12]),
+        NBTTag::TagLongArray(vec![13, 14]),
+    ];
+
+    for (i, tag) in tags.iter().enumerate() {
+        for &endianness in &[Endianness::Big, Endianness::Little] {
+            let mut bytes = encode_value(tag, endianness).unwrap();
+            let len = bytes.len();
+            bytes.push(0xFF);
+
+            assert_eq!(decode_value(&bytes, i as u8 + 1, endianness).unwrap(), (tag.clone(), len));
+            assert_eq!(skip_value(&bytes, i as u8 + 1, endianness).unwrap(), len);
+            assert!(skip_value(&bytes[..len - 1], i as u8 + 1, endianness).is_err());
+        }
+    }
+
+    assert!(decode_value(&[0x00], 0, Endianness::Big).is_err());
+    assert!(decode_value(&[0x00, 0x00], 3, Endianness::Big).is_err());
+}
+
+#[test]
+fn test_decode_float_byte_order() {
+    // 1.0f32 is 0x3F800000 and 1.0f64 is 0x3FF0000000000000, least significant byte first in little endian
+    assert_eq!(decode_value(&[0x00, 0x00, 0x80, 0x3F], 5, Endianness::Little).unwrap(), (NBTTag::TagFloat(1.0), 4));
+    assert_eq!(decode_value(&[0x3F, 0x80, 0x00, 0x00], 5, Endianness::Big).unwrap(), (NBTTag::TagFloat(1.0), 4));
+    assert_eq!(decode_value(&[0, 0, 0, 0, 0, 0, 0xF0, 0x3F], 6, Endianness::Little).unwrap(), (NBTTag::TagDouble(1.0), 8));
+    assert_eq!(decode_value(&[0x3F, 0xF0, 0, 0, 0, 0, 0, 0], 6, Endianness::Big).unwrap(), (NBTTag::TagDouble(1.0), 8));
+
+    // A little endian file with the float "f"
+    let input = vec![0x0A, 0x00, 0x00, 0x05, 0x01, 0x00, 0x66, 0x00, 0x00, 0x80, 0x3F, 0x00];
+    let little = ReadOptions { endianness: Endianness::Little, ..ReadOptions::default() };
+    assert_eq!(read_nbt_file(&input, &little).unwrap().1.unwrap().root.get("f"), Some(&NBTTag::TagFloat(1.0)));
+}
+
+#[test]
+fn test_read_nested_lists() {
+    // A list of two lists of bytes, the second of which is empty and declares TagEnd elements
+    let input = vec![0x09, 0x09, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+    assert_eq!(read_headless_nbt(&input).unwrap(),
+               NBTTag::TagList(vec![NBTTag::TagList(vec![NBTTag::TagByte(5)]), NBTTag::TagList(vec![])]));
 }
@@ -0,0 +1,435 @@
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use compression::{self, Compression};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use error::NBTError;
+use file::NBTFile;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use write;
+use NBTTag;
+
+const SECTOR_SIZE: usize = 4096;
+const CHUNK_COUNT: usize = 1024;
+
+// Set on the compression type of chunks too large for the region file, which the game stores in a
+// c.<x>.<z>.mcc file next to it instead
+const EXTERNAL_FLAG: u8 = 0x80;
+
+// A chunk as it is stored in the region file, still compressed
+#[derive(Debug, PartialEq, Clone)]
+struct RawChunk {
+    compression: u8,
+    data: Vec<u8>,
+}
+
+// A Minecraft Anvil region file (.mca), holding up to 32x32 chunks. Chunks are kept compressed
+// and are only decompressed and parsed when requested.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Region {
+    chunks: Vec<Option<RawChunk>>,
+    timestamps: Vec<u32>,
+    // The directory and region coordinates of a region read by from_path, used to find the files
+    // of external chunks
+    origin: Option<(PathBuf, i32, i32)>,
+}
+
+impl Region {
+    pub fn new() -> Region {
+        Region {
+            chunks: vec![None; CHUNK_COUNT],
+            timestamps: vec![0; CHUNK_COUNT],
+            origin: None,
+        }
+    }
+
+    // Reads the region file at path. If it is named like the game names them (r.<x>.<z>.mca),
+    // chunks stored in external files can be read as well.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Region, NBTError> {
+        let path = path.as_ref();
+        let mut region = Region::from_file(&mut File::open(path)?)?;
+
+        region.origin = region_coordinates(path)
+            .map(|(x, z)| (path.parent().map(Path::to_path_buf).unwrap_or_default(), x, z));
+
+        Ok(region)
+    }
+
+    pub fn from_file(file: &mut File) -> Result<Region, NBTError> {
+        let mut bytes: Vec<u8> = Vec::new();
+
+        file.read_to_end(&mut bytes)?;
+
+        Region::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Region, NBTError> {
+        let mut region = Region::new();
+
+        // The game creates empty region files before writing any chunk to them
+        if bytes.is_empty() {
+            return Ok(region);
+        }
+
+        if bytes.len() < 2 * SECTOR_SIZE {
+            return Err(NBTError::InvalidRegion("Header is truncated".to_owned()));
+        }
+
+        for index in 0..CHUNK_COUNT {
+            let location = BigEndian::read_u32(&bytes[index * 4..]);
+            region.timestamps[index] = BigEndian::read_u32(&bytes[SECTOR_SIZE + index * 4..]);
+
+            if location == 0 {
+                continue;
+            }
+
+            let start = (location >> 8) as usize * SECTOR_SIZE;
+
+            if start < 2 * SECTOR_SIZE || start + 5 > bytes.len() {
+                return Err(NBTError::InvalidRegion(format!("Chunk {} starts outside of the file", index)));
+            }
+
+            // The length includes the compression type byte
+            let len = BigEndian::read_u32(&bytes[start..]) as usize;
+
+            if len == 0 || start + 4 + len > bytes.len() {
+                return Err(NBTError::InvalidRegion(format!("Chunk {} ends outside of the file", index)));
+            }
+
+            region.chunks[index] = Some(RawChunk {
+                compression: bytes[start + 4],
+                data: bytes[start + 5..start + 4 + len].to_vec(),
+            });
+        }
+
+        Ok(region)
+    }
+
+    // Reads the chunk at the given chunk coordinates. Coordinates may be absolute, as only their
+    // position within the region (the lower 5 bits) is used.
+    pub fn get_chunk(&self, x: i32, z: i32) -> Result<Option<NBTTag>, NBTError> {
+        let chunk = match self.chunks[chunk_index(x, z)] {
+            Some(ref chunk) => chunk,
+            None => return Ok(None),
+        };
+
+        if chunk.compression & EXTERNAL_FLAG != 0 {
+            let (directory, region_x, region_z) = self.origin.as_ref()
+                .ok_or_else(|| NBTError::InvalidRegion("Chunk is stored in an external file, but the region was not read from a path".to_owned()))?;
+            let (chunk_x, chunk_z) = external_coordinates(*region_x, *region_z, x, z)
+                .ok_or_else(|| NBTError::InvalidRegion("Region coordinates are too large to locate an external chunk".to_owned()))?;
+            let path = directory.join(format!("c.{}.{}.mcc", chunk_x, chunk_z));
+
+            let mut data = Vec::new();
+            File::open(path)?.read_to_end(&mut data)?;
+
+            let bytes = compression::decompress(&data, compression_from_id(chunk.compression & !EXTERNAL_FLAG)?)?;
+            return Ok(Some(NBTFile::from_bytes(&bytes)?.root));
+        }
+
+        let bytes = compression::decompress(&chunk.data, compression_from_id(chunk.compression)?)?;
+
+        Ok(Some(NBTFile::from_bytes(&bytes)?.root))
+    }
+
+    // Iterates over the chunks that are present, with their coordinates within the region (0 to 31),
+    // in chunk index order. Every chunk is only decompressed and parsed once the iterator reaches
+    // it, so a chunk that fails to read does not keep the others from being read.
+    pub fn chunks(&self) -> impl Iterator<Item = Result<(i32, i32, NBTTag), NBTError>> + '_ {
+        (0..CHUNK_COUNT)
+            .filter(move |&index| self.chunks[index].is_some())
+            .map(move |index| self.read_present_chunk(index))
+    }
+
+    // Reads all present chunks like chunks does, in parallel on the rayon thread pool, which pays
+    // off as decompressing and parsing is CPU-bound. The results are in chunk index order.
+    #[cfg(feature = "rayon")]
+    pub fn par_chunks(&self) -> Vec<Result<(i32, i32, NBTTag), NBTError>> {
+        let indices: Vec<usize> = (0..CHUNK_COUNT).filter(|&index| self.chunks[index].is_some()).collect();
+
+        indices.par_iter().map(|&index| self.read_present_chunk(index)).collect()
+    }
+
+    fn read_present_chunk(&self, index: usize) -> Result<(i32, i32, NBTTag), NBTError> {
+        let (x, z) = ((index % 32) as i32, (index / 32) as i32);
+
+        self.get_chunk(x, z).map(|chunk| (x, z, chunk.unwrap()))
+    }
+
+    // Stores the chunk, compressed using zlib, and sets its timestamp to the current time. The
+    // chunk is required to be a TagCompound.
+    pub fn set_chunk(&mut self, x: i32, z: i32, chunk: &NBTTag) -> Result<(), NBTError> {
+        if let NBTTag::TagCompound(_) = *chunk {
+            let bytes = write::write_tag(chunk, true, true, Some(""))?;
+            let index = chunk_index(x, z);
+
+            self.chunks[index] = Some(RawChunk {
+                compression: 2,
+                data: compression::compress(&bytes, Compression::Zlib)?,
+            });
+            self.timestamps[index] = now();
+
+            return Ok(());
+        }
+
+        Err(NBTError::InvalidRoot { found: write::get_tag_id(chunk) })
+    }
+
+    // The time the chunk was last saved, in seconds since the unix epoch, or None if there is no
+    // chunk at the coordinates
+    pub fn chunk_timestamp(&self, x: i32, z: i32) -> Option<u32> {
+        let index = chunk_index(x, z);
+
+        self.chunks[index].as_ref().map(|_| self.timestamps[index])
+    }
+
+    // Overrides the timestamp set_chunk stored, e.g. to keep the time of a copied chunk
+    pub fn set_chunk_timestamp(&mut self, x: i32, z: i32, timestamp: u32) {
+        self.timestamps[chunk_index(x, z)] = timestamp;
+    }
+
+    pub fn remove_chunk(&mut self, x: i32, z: i32) {
+        let index = chunk_index(x, z);
+
+        self.chunks[index] = None;
+        self.timestamps[index] = 0;
+    }
+
+    // Packs all chunks into consecutive sectors following the header, in chunk index order.
+    // Chunks that were not changed keep their compressed data as it was read.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, NBTError> {
+        let mut output = vec![0; 2 * SECTOR_SIZE];
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            BigEndian::write_u32(&mut output[SECTOR_SIZE + index * 4..], self.timestamps[index]);
+
+            let chunk = match *chunk {
+                Some(ref chunk) => chunk,
+                None => continue,
+            };
+
+            let offset = output.len() / SECTOR_SIZE;
+            let sectors = (chunk.data.len() + 5).div_ceil(SECTOR_SIZE);
+
+            if sectors > 255 {
+                return Err(NBTError::InvalidRegion(format!("Chunk {} does not fit in 255 sectors", index)));
+            }
+
+            BigEndian::write_u32(&mut output[index * 4..], (offset << 8 | sectors) as u32);
+
+            output.write_u32::<BigEndian>(chunk.data.len() as u32 + 1)?;
+            output.push(chunk.compression);
+            output.extend_from_slice(&chunk.data);
+            output.resize((offset + sectors) * SECTOR_SIZE, 0);
+        }
+
+        Ok(output)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), NBTError> {
+        let mut file = File::create(path)?;
+
+        file.write_all(self.to_bytes()?.as_slice())?;
+
+        Ok(())
+    }
+}
+
+impl Default for Region {
+    fn default() -> Region {
+        Region::new()
+    }
+}
+
+fn chunk_index(x: i32, z: i32) -> usize {
+    ((x & 31) + (z & 31) * 32) as usize
+}
+
+// The absolute chunk coordinates naming the file of an external chunk, or None if those don't fit
+// in an i32, which a region name like r.100000000.0.mca would make them
+fn external_coordinates(region_x: i32, region_z: i32, x: i32, z: i32) -> Option<(i32, i32)> {
+    let absolute = |region: i32, chunk: i32| region.checked_mul(32)?.checked_add(chunk & 31);
+
+    Some((absolute(region_x, x)?, absolute(region_z, z)?))
+}
+
+// Parses the coordinates out of a region file name like r.-1.2.mca
+fn region_coordinates(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+
+    match (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()) {
+        (x, z, None) => Some((x, z)),
+        _ => None,
+    }
+}
+
+fn now() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() as u32).unwrap_or(0)
+}
+
+fn compression_from_id(id: u8) -> Result<Compression, NBTError> {
+    match id {
+        1 => Ok(Compression::Gzip),
+        2 => Ok(Compression::Zlib),
+        3 => Ok(Compression::None),
+        _ => Err(NBTError::UnknownCompression(id)),
+    }
+}
+
+#[cfg(test)]
+fn region_with_chunk(index: usize, chunk: &NBTFile) -> Vec<u8> {
+    let data = compression::compress(&chunk.as_bytes().unwrap(), Compression::Zlib).unwrap();
+    let mut bytes = vec![0; 3 * SECTOR_SIZE];
+
+    BigEndian::write_u32(&mut bytes[index * 4..], 2 << 8 | 1);
+    BigEndian::write_u32(&mut bytes[SECTOR_SIZE + index * 4..], 1234);
+    BigEndian::write_u32(&mut bytes[2 * SECTOR_SIZE..], data.len() as u32 + 1);
+    bytes[2 * SECTOR_SIZE + 4] = 2;
+    bytes[2 * SECTOR_SIZE + 5..2 * SECTOR_SIZE + 5 + data.len()].copy_from_slice(&data);
+
+    bytes
+}
+
+#[test]
+fn test_region_get_chunk() {
+    use indexmap::IndexMap;
+
+    let mut root = IndexMap::new();
+    root.insert("DataVersion".to_owned(), NBTTag::TagInt(3465));
+    let chunk = NBTFile::new(String::new(), Some(NBTTag::TagCompound(root)));
+
+    let region = Region::from_bytes(&region_with_chunk(chunk_index(1, 2), &chunk)).unwrap();
+
+    assert_eq!(region.get_chunk(1, 2).unwrap(), Some(chunk.root.clone()));
+    assert_eq!(region.get_chunk(33, -30).unwrap(), Some(chunk.root));
+    assert_eq!(region.get_chunk(0, 0).unwrap(), None);
+}
+
+#[test]
+fn test_region_chunks() {
+    use from_snbt;
+
+    let mut region = Region::new();
+    region.set_chunk(3, 0, &from_snbt("{xPos:3}").unwrap()).unwrap();
+    region.set_chunk(1, 2, &from_snbt("{xPos:1}").unwrap()).unwrap();
+    region.chunks[chunk_index(5, 5)] = Some(RawChunk { compression: 9, data: Vec::new() });
+
+    let chunks: Vec<_> = region.chunks().collect();
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[0].as_ref().unwrap(), &(3, 0, from_snbt("{xPos:3}").unwrap()));
+    assert_eq!(chunks[1].as_ref().unwrap(), &(1, 2, from_snbt("{xPos:1}").unwrap()));
+    assert!(chunks[2].is_err());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_region_par_chunks() {
+    let mut region = Region::new();
+    for index in 0..100 {
+        let chunk = NBTTag::TagCompound(vec![("index".to_owned(), NBTTag::TagInt(index))].into_iter().collect());
+        region.set_chunk(index % 32, index / 32, &chunk).unwrap();
+    }
+    region.chunks[chunk_index(5, 5)] = Some(RawChunk { compression: 9, data: Vec::new() });
+
+    let describe = |results: Vec<Result<(i32, i32, NBTTag), NBTError>>| -> Vec<Result<(i32, i32, NBTTag), String>> {
+        results.into_iter().map(|result| result.map_err(|err| err.to_string())).collect()
+    };
+
+    let parallel = describe(region.par_chunks());
+    assert_eq!(parallel.len(), 101);
+    assert_eq!(parallel, describe(region.chunks().collect()));
+}
+
+#[test]
+fn test_region_rejects_truncated_header() {
+    assert!(Region::from_bytes(&[0; 100]).is_err());
+    assert!(Region::from_bytes(&[]).unwrap().get_chunk(0, 0).unwrap().is_none());
+}
+
+#[test]
+fn test_region_round_trip_keeps_other_chunks() {
+    use indexmap::IndexMap;
+
+    let mut root = IndexMap::new();
+    root.insert("xPos".to_owned(), NBTTag::TagInt(0));
+    let chunk = NBTFile::new(String::new(), Some(NBTTag::TagCompound(root)));
+
+    let mut region = Region::from_bytes(&region_with_chunk(0, &chunk)).unwrap();
+
+    let mut big = IndexMap::new();
+    big.insert("Data".to_owned(), NBTTag::TagLongArray((0..2000).collect()));
+    region.set_chunk(5, 0, &NBTTag::TagCompound(big.clone())).unwrap();
+    region.set_chunk(6, 0, &NBTTag::TagCompound(big)).unwrap();
+    region.remove_chunk(6, 0);
+
+    let bytes = region.to_bytes().unwrap();
+    let reloaded = Region::from_bytes(&bytes).unwrap();
+
+    assert_eq!(bytes.len() % SECTOR_SIZE, 0);
+    assert_eq!(reloaded.chunks[0], region.chunks[0]);
+    assert_eq!(reloaded.chunk_timestamp(0, 0), Some(1234));
+    assert_eq!(reloaded.chunk_timestamp(6, 0), None);
+    assert_eq!(reloaded, region);
+    assert_eq!(reloaded.get_chunk(0, 0).unwrap(), Some(chunk.root));
+    assert!(reloaded.get_chunk(6, 0).unwrap().is_none());
+    assert!(region.set_chunk(0, 0, &NBTTag::TagInt(0)).is_err());
+
+    region.set_chunk_timestamp(5, 0, 42);
+    assert_eq!(Region::from_bytes(&region.to_bytes().unwrap()).unwrap().chunk_timestamp(5, 0), Some(42));
+}
+
+#[test]
+fn test_region_external_chunk() {
+    use indexmap::IndexMap;
+
+    let directory = ::std::env::temp_dir().join(format!("rust_nbt_mcc_{}", ::std::process::id()));
+    ::std::fs::create_dir_all(&directory).unwrap();
+
+    let mut root = IndexMap::new();
+    root.insert("xPos".to_owned(), NBTTag::TagInt(34));
+    let chunk = NBTFile::new(String::new(), Some(NBTTag::TagCompound(root)));
+
+    // The region file only holds the compression type, the data is in c.34.-29.mcc
+    let index = chunk_index(2, 3);
+    let mut bytes = vec![0; 3 * SECTOR_SIZE];
+    BigEndian::write_u32(&mut bytes[index * 4..], 2 << 8 | 1);
+    BigEndian::write_u32(&mut bytes[2 * SECTOR_SIZE..], 1);
+    bytes[2 * SECTOR_SIZE + 4] = EXTERNAL_FLAG | 2;
+
+    let region_path = directory.join("r.1.-1.mca");
+    File::create(&region_path).unwrap().write_all(&bytes).unwrap();
+    let data = compression::compress(&chunk.as_bytes().unwrap(), Compression::Zlib).unwrap();
+    File::create(directory.join("c.34.-29.mcc")).unwrap().write_all(&data).unwrap();
+
+    let result = Region::from_path(&region_path).and_then(|region| region.get_chunk(34, -29));
+    let unlocated = Region::from_bytes(&bytes).unwrap().get_chunk(2, 3);
+    ::std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(result.unwrap(), Some(chunk.root));
+    assert!(unlocated.is_err());
+}
+
+#[test]
+fn test_region_external_chunk_overflow() {
+    assert_eq!(external_coordinates(1, -1, 34, -29), Some((34, -29)));
+    assert_eq!(external_coordinates(i32::MIN / 32, 0, 0, 0), Some((i32::MIN, 0)));
+    assert_eq!(external_coordinates(i32::MAX / 32, 0, 31, 0), Some((i32::MAX, 0)));
+    assert_eq!(external_coordinates(i32::MAX / 32 + 1, 0, 0, 0), None);
+    assert_eq!(external_coordinates(0, i32::MIN / 32 - 1, 0, 0), None);
+
+    // A region named beyond those coordinates reports the chunk as invalid instead of panicking
+    let index = chunk_index(2, 3);
+    let mut bytes = vec![0; 3 * SECTOR_SIZE];
+    BigEndian::write_u32(&mut bytes[index * 4..], 2 << 8 | 1);
+    BigEndian::write_u32(&mut bytes[2 * SECTOR_SIZE..], 1);
+    bytes[2 * SECTOR_SIZE + 4] = EXTERNAL_FLAG | 2;
+
+    let mut region = Region::from_bytes(&bytes).unwrap();
+    region.origin = Some((PathBuf::new(), 100_000_000, 0));
+
+    assert_eq!(region.get_chunk(2, 3).unwrap_err().to_string(),
+               NBTError::InvalidRegion("Region coordinates are too large to locate an external chunk".to_owned()).to_string());
+}
@@ -0,0 +1,193 @@
+// Reading of Sponge schematics (.schem), as written by WorldEdit. Versions 1 and 2 keep the palette
+// and block data at the root, while version 3 nests everything in a Schematic compound and keeps
+// them in Blocks as Palette and Data.
+
+use error::NBTError;
+use file::NBTFile;
+use std::path::Path;
+use varint;
+use write::get_tag_id;
+use NBTTag;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schematic {
+    width: u16,
+    height: u16,
+    length: u16,
+    palette: Vec<String>,
+    blocks: Vec<u32>,
+    file: NBTFile,
+}
+
+impl Schematic {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Schematic, NBTError> {
+        Schematic::from_file(NBTFile::from_path(path)?)
+    }
+
+    pub fn from_file(file: NBTFile) -> Result<Schematic, NBTError> {
+        let root = match file.root.get("Schematic") {
+            Some(schematic @ NBTTag::TagCompound(_)) => schematic,
+            _ => &file.root,
+        };
+
+        let (palette_tag, data) = match root.get("Blocks") {
+            Some(blocks) => (blocks.get("Palette"), blocks.get("Data")),
+            None => (root.get("Palette"), root.get("BlockData")),
+        };
+
+        let width = dimension(root, "Width")?;
+        let height = dimension(root, "Height")?;
+        let length = dimension(root, "Length")?;
+
+        let mut palette = Vec::new();
+        match palette_tag {
+            Some(NBTTag::TagCompound(map)) => {
+                palette.resize(map.len(), String::new());
+
+                for (state, index) in map {
+                    match *index {
+                        NBTTag::TagInt(index) if index >= 0 && (index as usize) < map.len() => palette[index as usize] = state.clone(),
+                        _ => return Err(NBTError::InvalidValue(format!("Palette index of {} is out of range", state))),
+                    }
+                }
+            }
+            Some(other) => return Err(NBTError::UnexpectedType { expected: 10, found: get_tag_id(other) }),
+            None => return Err(NBTError::InvalidValue("Schematic has no palette".to_owned())),
+        }
+
+        let blocks = match data {
+            Some(NBTTag::TagByteArray(bytes)) => decode_varints(bytes)?,
+            Some(other) => return Err(NBTError::UnexpectedType { expected: 7, found: get_tag_id(other) }),
+            None => return Err(NBTError::InvalidValue("Schematic has no block data".to_owned())),
+        };
+
+        let volume = usize::from(width) * usize::from(height) * usize::from(length);
+        if blocks.len() != volume {
+            return Err(NBTError::InvalidValue(format!(
+                "Schematic has {} blocks, but its dimensions require {}", blocks.len(), volume)));
+        }
+
+        if let Some(&index) = blocks.iter().find(|&&index| index as usize >= palette.len()) {
+            return Err(NBTError::InvalidValue(format!("Block refers to palette index {}, which is out of range", index)));
+        }
+
+        Ok(Schematic { width, height, length, palette, blocks, file })
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    // The block states of the palette, like `minecraft:oak_stairs[facing=east]`, by their index
+    pub fn palette(&self) -> &[String] {
+        &self.palette
+    }
+
+    // The palette index of every block, in YZX order
+    pub fn blocks(&self) -> &[u32] {
+        &self.blocks
+    }
+
+    pub fn block_at(&self, x: u16, y: u16, z: u16) -> Option<&str> {
+        if x >= self.width || y >= self.height || z >= self.length {
+            return None;
+        }
+
+        let index = (usize::from(y) * usize::from(self.length) + usize::from(z)) * usize::from(self.width) + usize::from(x);
+
+        Some(&self.palette[self.blocks[index] as usize])
+    }
+
+    // The file the schematic was read from, for the tags that have no accessor
+    pub fn file(&self) -> &NBTFile {
+        &self.file
+    }
+}
+
+// Dimensions are TagShorts, but are meant to be read as unsigned
+fn dimension(root: &NBTTag, key: &str) -> Result<u16, NBTError> {
+    match root.get(key) {
+        Some(&NBTTag::TagShort(value)) => Ok(value as u16),
+        Some(other) => Err(NBTError::UnexpectedType { expected: 2, found: get_tag_id(other) }),
+        None => Err(NBTError::InvalidValue(format!("Schematic has no {}", key))),
+    }
+}
+
+// Decodes the varints that BlockData consists of back to back
+fn decode_varints(bytes: &[i8]) -> Result<Vec<u32>, NBTError> {
+    let bytes: Vec<u8> = bytes.iter().map(|&byte| byte as u8).collect();
+    let mut values = Vec::new();
+    let mut rest = &bytes[..];
+
+    while !rest.is_empty() {
+        let (value, len) = varint::read_varint(rest)?;
+
+        if value < 0 {
+            return Err(NBTError::InvalidValue(format!("Block refers to negative palette index {}", value)));
+        }
+
+        values.push(value as u32);
+        rest = &rest[len..];
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+fn test_schematic(data: Vec<i8>) -> NBTFile {
+    use file::NBTFileBuilder;
+    use indexmap::IndexMap;
+
+    let mut palette = IndexMap::new();
+    palette.insert("minecraft:air".to_owned(), NBTTag::TagInt(0));
+    palette.insert("minecraft:stone".to_owned(), NBTTag::TagInt(1));
+
+    NBTFileBuilder::new()
+        .name("Schematic")
+        .put("Version", 2)
+        .put("Width", 2i16)
+        .put("Height", 1i16)
+        .put("Length", 2i16)
+        .put("Palette", NBTTag::TagCompound(palette))
+        .put("BlockData", NBTTag::TagByteArray(data))
+        .build()
+}
+
+#[test]
+fn test_from_path() {
+    use compression::Compression;
+
+    let path = ::std::env::temp_dir().join(format!("rust_nbt_schem_{}.schem", ::std::process::id()));
+
+    test_schematic(vec![0, 1, 1, 0]).with_compression(Compression::Gzip).write_to_path(&path).unwrap();
+    let schematic = Schematic::from_path(&path);
+    ::std::fs::remove_file(&path).unwrap();
+    let schematic = schematic.unwrap();
+
+    assert_eq!((schematic.width(), schematic.height(), schematic.length()), (2, 1, 2));
+    assert_eq!(schematic.palette(), &["minecraft:air".to_owned(), "minecraft:stone".to_owned()][..]);
+    assert_eq!(schematic.blocks(), &[0, 1, 1, 0][..]);
+    assert_eq!(schematic.block_at(1, 0, 0), Some("minecraft:stone"));
+    assert_eq!(schematic.block_at(1, 0, 1), Some("minecraft:air"));
+    assert_eq!(schematic.block_at(2, 0, 0), None);
+}
+
+#[test]
+fn test_invalid_block_data() {
+    assert!(Schematic::from_file(test_schematic(vec![0, 1, 1])).is_err());
+    assert!(Schematic::from_file(test_schematic(vec![0, 1, 2, 0])).is_err());
+    assert!(Schematic::from_file(test_schematic(vec![0, 1, 1, 0x80u8 as i8])).is_err());
+}
+
+#[test]
+fn test_decode_varints() {
+    assert_eq!(decode_varints(&[0x00, 0x7F, 0x80u8 as i8, 0x01, 0xFFu8 as i8, 0x7F]).unwrap(), vec![0, 127, 128, 16383]);
+}
@@ -0,0 +1,183 @@
+// Lightweight descriptions of the shape a tree is expected to have, like the keys and types of a
+// config file, to report every way in which a tree deviates from it at once
+
+use indexmap::IndexMap;
+use path;
+use std::fmt;
+use NBTTag;
+use TagType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    // Any tag at all
+    Any,
+    // Any tag of the type, without looking inside lists and compounds
+    Type(TagType),
+    // A list of which every element matches the schema
+    List(Box<Schema>),
+    Compound(CompoundSchema),
+}
+
+// The entries a compound is expected to have. Keys that are not listed are errors, unless
+// allow_extra was called.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompoundSchema {
+    fields: IndexMap<String, (Schema, bool)>,
+    allow_extra: bool,
+}
+
+// A single way in which a tree does not match a schema, addressed by the path of the tag, in the
+// notation of path::segments
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    Missing(String),
+    WrongType { path: String, expected: TagType, found: TagType },
+    Unexpected(String),
+}
+
+impl Schema {
+    pub fn list(elements: Schema) -> Schema {
+        Schema::List(Box::new(elements))
+    }
+
+    // Checks tag against the schema, returning all mismatches in the order of a depth first walk
+    pub fn validate(&self, tag: &NBTTag) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        validate_at(self, tag, "", &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl CompoundSchema {
+    pub fn new() -> CompoundSchema {
+        CompoundSchema::default()
+    }
+
+    pub fn required<K: Into<String>>(mut self, key: K, schema: Schema) -> CompoundSchema {
+        self.fields.insert(key.into(), (schema, true));
+        self
+    }
+
+    pub fn optional<K: Into<String>>(mut self, key: K, schema: Schema) -> CompoundSchema {
+        self.fields.insert(key.into(), (schema, false));
+        self
+    }
+
+    // Accepts keys that are not described by the schema, without checking their values
+    pub fn allow_extra(mut self) -> CompoundSchema {
+        self.allow_extra = true;
+        self
+    }
+}
+
+impl From<CompoundSchema> for Schema {
+    fn from(schema: CompoundSchema) -> Schema {
+        Schema::Compound(schema)
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemaError::Missing(ref path) => write!(f, "{} is missing", path),
+            SchemaError::WrongType { ref path, expected, found } => {
+                write!(f, "{} is required to be {} but is {}", display_path(path), expected.name(), found.name())
+            }
+            SchemaError::Unexpected(ref path) => write!(f, "{} is not expected", path),
+        }
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "The root"
+    } else {
+        path
+    }
+}
+
+fn validate_at(schema: &Schema, tag: &NBTTag, current: &str, errors: &mut Vec<SchemaError>) {
+    let expected = match *schema {
+        Schema::Any => return,
+        Schema::Type(tag_type) => tag_type,
+        Schema::List(_) => TagType::List,
+        Schema::Compound(_) => TagType::Compound,
+    };
+
+    if tag.tag_type() != expected {
+        errors.push(SchemaError::WrongType { path: current.to_owned(), expected, found: tag.tag_type() });
+        return;
+    }
+
+    match (schema, tag) {
+        (Schema::List(elements), NBTTag::TagList(list)) => {
+            for (index, elem) in list.iter().enumerate() {
+                validate_at(elements, elem, &path::child_index(current, index), errors);
+            }
+        }
+        (Schema::Compound(compound), NBTTag::TagCompound(map)) => {
+            for (key, &(ref field, required)) in &compound.fields {
+                match map.get(key) {
+                    Some(value) => validate_at(field, value, &path::child_key(current, key), errors),
+                    None if required => errors.push(SchemaError::Missing(path::child_key(current, key))),
+                    None => (),
+                }
+            }
+
+            if !compound.allow_extra {
+                for key in map.keys().filter(|key| !compound.fields.contains_key(key.as_str())) {
+                    errors.push(SchemaError::Unexpected(path::child_key(current, key)));
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+fn player_schema() -> Schema {
+    let item = CompoundSchema::new()
+        .required("id", Schema::Type(TagType::String))
+        .required("Count", Schema::Type(TagType::Byte))
+        .optional("tag", Schema::Type(TagType::Compound));
+
+    CompoundSchema::new()
+        .required("Health", Schema::Type(TagType::Float))
+        .required("Pos", Schema::list(Schema::Type(TagType::Double)))
+        .required("Inventory", Schema::list(item.into()))
+        .optional("CustomName", Schema::Type(TagType::String))
+        .into()
+}
+
+#[test]
+fn test_validate() {
+    use from_snbt;
+
+    let player = from_snbt("{Health:20.0f,Pos:[1.0d,64.0d,2.0d],Inventory:[{id:\"stone\",Count:3b,tag:{}}]}").unwrap();
+    assert_eq!(player_schema().validate(&player), Ok(()));
+
+    let player = from_snbt("{Health:20,Pos:[64.0f],Inventory:[{Count:3b,Slot:0b}],Motion:[]}").unwrap();
+    assert_eq!(player_schema().validate(&player), Err(vec![
+        SchemaError::WrongType { path: "Health".to_owned(), expected: TagType::Float, found: TagType::Int },
+        SchemaError::WrongType { path: "Pos[0]".to_owned(), expected: TagType::Double, found: TagType::Float },
+        SchemaError::Missing("Inventory[0].id".to_owned()),
+        SchemaError::Unexpected("Inventory[0].Slot".to_owned()),
+        SchemaError::Unexpected("Motion".to_owned()),
+    ]));
+}
+
+#[test]
+fn test_validate_allow_extra() {
+    use from_snbt;
+
+    let schema: Schema = CompoundSchema::new().required("a", Schema::Any).allow_extra().into();
+
+    assert_eq!(schema.validate(&from_snbt("{a:[1,2],b:1}").unwrap()), Ok(()));
+    assert_eq!(schema.validate(&NBTTag::TagInt(1)).unwrap_err()[0].to_string(),
+               "The root is required to be TAG_Compound but is TAG_Int");
+}
@@ -0,0 +1,599 @@
+use error::NBTError;
+use indexmap::IndexMap;
+use read::MAX_DEPTH;
+use std::mem;
+use std::str::CharIndices;
+use write::get_tag_id;
+use NBTTag;
+
+// Parses stringified NBT like used in Minecraft commands, e.g. `{id:"minecraft:stone",Count:1b}`.
+// Unquoted values are numbers when they match one of the number forms (with an optional b, s,
+// l, f or d suffix), true and false are bytes and everything else is a string. `[B;...]`,
+// `[I;...]` and `[L;...]` are arrays, other `[...]` are lists.
+pub fn from_snbt(input: &str) -> Result<NBTTag, NBTError> {
+    let mut parser = Parser { input, pos: 0 };
+
+    let tag = parser.read_value(0)?;
+    parser.skip_whitespace();
+
+    if parser.pos < input.len() {
+        return Err(parser.error("Trailing data after value"));
+    }
+
+    Ok(tag)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &'static str) -> NBTError {
+        NBTError::InvalidSnbt { offset: self.pos, msg }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, expected: char, msg: &'static str) -> Result<(), NBTError> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(msg))
+        }
+    }
+
+    // Consumes a comma if one follows, returning whether the sequence continues
+    fn read_separator(&mut self, close: char) -> Result<bool, NBTError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(c) if c == close => Ok(false),
+            _ => Err(self.error("Expected ',' or closing bracket")),
+        }
+    }
+
+    // depth is the number of compounds and lists the value is nested in, which is limited to
+    // MAX_DEPTH like in the binary reader, so deeply nested input cannot overflow the stack
+    fn read_value(&mut self, depth: usize) -> Result<NBTTag, NBTError> {
+        self.skip_whitespace();
+
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+
+        match self.peek() {
+            Some('{') => self.read_compound(depth),
+            Some('[') => self.read_list_or_array(depth),
+            Some('"') | Some('\'') => Ok(NBTTag::TagString(self.read_quoted()?)),
+            Some(_) => {
+                let start = self.pos;
+                let value = self.read_unquoted();
+
+                if value.is_empty() {
+                    self.pos = start;
+                    return Err(self.error("Expected value"));
+                }
+
+                Ok(unquoted_value(value))
+            }
+            None => Err(self.error("Expected value")),
+        }
+    }
+
+    fn read_compound(&mut self, depth: usize) -> Result<NBTTag, NBTError> {
+        self.pos += 1;
+
+        let mut map = IndexMap::new();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(NBTTag::TagCompound(map));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            // Only quoted keys may be empty, like the "" key that to_snbt writes for them
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.read_quoted()?,
+                _ => match self.read_unquoted() {
+                    "" => return Err(self.error("Expected key")),
+                    key => key.to_owned(),
+                },
+            };
+
+            self.expect(':', "Expected ':' after key")?;
+            map.insert(key, self.read_value(depth + 1)?);
+
+            if !self.read_separator('}')? {
+                break;
+            }
+        }
+
+        self.pos += 1;
+
+        Ok(NBTTag::TagCompound(map))
+    }
+
+    fn read_list_or_array(&mut self, depth: usize) -> Result<NBTTag, NBTError> {
+        self.pos += 1;
+
+        let rest = &self.input.as_bytes()[self.pos..];
+
+        if rest.len() >= 2 && rest[1] == b';' {
+            let array_type = match rest[0] {
+                b'B' => Some(1),
+                b'I' => Some(3),
+                b'L' => Some(4),
+                _ => None,
+            };
+
+            if let Some(elems_type) = array_type {
+                self.pos += 2;
+                return self.read_array(elems_type, depth);
+            }
+        }
+
+        let elems = self.read_elements(depth)?;
+
+        if let Some(first) = elems.first() {
+            if elems.iter().any(|elem| mem::discriminant(elem) != mem::discriminant(first)) {
+                return Err(self.error("Elements of a list are required to be of the same type"));
+            }
+        }
+
+        Ok(NBTTag::TagList(elems))
+    }
+
+    // Reads the values of an array, each of which has to be of the element type, e.g. 1 for the
+    // bytes of a TagByteArray
+    fn read_array(&mut self, elems_type: u8, depth: usize) -> Result<NBTTag, NBTError> {
+        let elems = self.read_elements(depth)?;
+
+        if elems.iter().any(|elem| get_tag_id(elem) != elems_type) {
+            return Err(self.error("Elements of an array are required to be of the array's type"));
+        }
+
+        let tag = match elems_type {
+            1 => NBTTag::TagByteArray(elems.into_iter().filter_map(|elem| match elem {
+                NBTTag::TagByte(value) => Some(value),
+                _ => None,
+            }).collect()),
+            3 => NBTTag::TagIntArray(elems.into_iter().filter_map(|elem| match elem {
+                NBTTag::TagInt(value) => Some(value),
+                _ => None,
+            }).collect()),
+            _ => NBTTag::TagLongArray(elems.into_iter().filter_map(|elem| match elem {
+                NBTTag::TagLong(value) => Some(value),
+                _ => None,
+            }).collect()),
+        };
+
+        Ok(tag)
+    }
+
+    // Reads comma separated values up to and including the closing bracket
+    fn read_elements(&mut self, depth: usize) -> Result<Vec<NBTTag>, NBTError> {
+        let mut elems = Vec::new();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(elems);
+        }
+
+        loop {
+            elems.push(self.read_value(depth + 1)?);
+
+            if !self.read_separator(']')? {
+                break;
+            }
+        }
+
+        self.pos += 1;
+
+        Ok(elems)
+    }
+
+    fn read_quoted(&mut self) -> Result<String, NBTError> {
+        let quote = self.peek();
+        self.pos += 1;
+
+        let mut value = String::new();
+        let mut chars = self.input[self.pos..].char_indices();
+
+        while let Some((i, c)) = chars.next() {
+            if Some(c) == quote {
+                self.pos += i + 1;
+                return Ok(value);
+            }
+
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+
+            let unescaped = match chars.next() {
+                Some((_, escaped)) if escaped == '\\' || escaped == '"' || escaped == '\'' => Some(escaped),
+                Some((_, 'b')) => Some('\u{8}'),
+                Some((_, 'f')) => Some('\u{c}'),
+                Some((_, 'n')) => Some('\n'),
+                Some((_, 'r')) => Some('\r'),
+                Some((_, 't')) => Some('\t'),
+                Some((_, 'x')) => read_hex(&mut chars, 2),
+                Some((_, 'u')) => read_hex(&mut chars, 4),
+                Some((_, 'U')) => read_hex(&mut chars, 8),
+                _ => None,
+            };
+
+            match unescaped {
+                Some(unescaped) => value.push(unescaped),
+                None => {
+                    self.pos += i;
+                    return Err(self.error("Invalid escape sequence"));
+                }
+            }
+        }
+
+        self.pos = self.input.len();
+
+        Err(self.error("Unterminated string"))
+    }
+
+    fn read_unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+
+        while let Some(c) = self.peek() {
+            if !is_unquoted_char(c) {
+                break;
+            }
+
+            self.pos += 1;
+        }
+
+        &self.input[start..self.pos]
+    }
+}
+
+// Reads the code point of a \\x, \\u or \\U escape with the given number of hex digits
+fn read_hex(chars: &mut CharIndices, digits: usize) -> Option<char> {
+    let mut code = 0;
+
+    for _ in 0..digits {
+        code = code * 16 + chars.next()?.1.to_digit(16)?;
+    }
+
+    char::from_u32(code)
+}
+
+// Converts a tag to SNBT that from_snbt, and Minecraft, read back into the same tag
+pub fn to_snbt(tag: &NBTTag) -> String {
+    let mut output = String::new();
+    write_snbt(tag, &mut output);
+    output
+}
+
+fn write_snbt(tag: &NBTTag, output: &mut String) {
+    match *tag {
+        NBTTag::TagEnd => (),
+        NBTTag::TagByte(value) => output.push_str(&format!("{}b", value)),
+        NBTTag::TagShort(value) => output.push_str(&format!("{}s", value)),
+        NBTTag::TagInt(value) => output.push_str(&value.to_string()),
+        NBTTag::TagLong(value) => output.push_str(&format!("{}L", value)),
+        NBTTag::TagFloat(value) => write_float(output, f64::from(value), &format!("{:?}", value), 'f'),
+        NBTTag::TagDouble(value) => write_float(output, value, &format!("{:?}", value), 'd'),
+        NBTTag::TagByteArray(ref value) => write_array(output, "B", value.iter().map(|value| format!("{}b", value))),
+        NBTTag::TagString(ref value) => output.push_str(&escape_snbt_string(value)),
+        NBTTag::TagList(ref value) => {
+            output.push('[');
+
+            for (i, elem) in value.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+
+                write_snbt(elem, output);
+            }
+
+            output.push(']');
+        }
+        NBTTag::TagCompound(ref value) => {
+            output.push('{');
+
+            for (i, (key, elem)) in value.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+
+                if !key.is_empty() && key.chars().all(is_unquoted_char) {
+                    output.push_str(key);
+                } else {
+                    output.push_str(&escape_snbt_string(key));
+                }
+
+                output.push(':');
+                write_snbt(elem, output);
+            }
+
+            output.push('}');
+        }
+        NBTTag::TagIntArray(ref value) => write_array(output, "I", value.iter().map(|value| value.to_string())),
+        NBTTag::TagLongArray(ref value) => write_array(output, "L", value.iter().map(|value| format!("{}L", value))),
+    }
+}
+
+fn write_array<I: Iterator<Item = String>>(output: &mut String, prefix: &str, elems: I) {
+    output.push('[');
+    output.push_str(prefix);
+    output.push(';');
+    output.push_str(&elems.collect::<Vec<String>>().join(","));
+    output.push(']');
+}
+
+// Quotes a string like Minecraft does: with double quotes, unless the string contains a double
+// quote before any single quote, in which case single quotes are used. Backslashes and the chosen
+// quote are escaped with a backslash and control characters with \\n, \\t etc. or \\xNN.
+fn escape_snbt_string(value: &str) -> String {
+    let quote = match value.find(['"', '\'']) {
+        Some(i) if value[i..].starts_with('"') => '\'',
+        _ => '"',
+    };
+
+    let mut output = String::with_capacity(value.len() + 2);
+    output.push(quote);
+
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\u{8}' => output.push_str("\\b"),
+            '\u{c}' => output.push_str("\\f"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c == quote => {
+                output.push('\\');
+                output.push(c);
+            }
+            c if c.is_control() => output.push_str(&format!("\\x{:02x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+
+    output.push(quote);
+    output
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '+'
+}
+
+// Writes a float or double formatted as Debug, which always keeps the fraction of whole numbers.
+// Values that have no digits are spelled the way Java does, like Minecraft itself writes them.
+fn write_float(output: &mut String, value: f64, formatted: &str, suffix: char) {
+    match value {
+        value if value.is_nan() => output.push_str("NaN"),
+        value if value == f64::INFINITY => output.push_str("Infinity"),
+        value if value == f64::NEG_INFINITY => output.push_str("-Infinity"),
+        _ => output.push_str(formatted),
+    }
+
+    output.push(suffix);
+}
+
+// The values of floats and doubles that are spelled out, see write_float
+fn special_float(number: &str) -> Option<f64> {
+    match number {
+        "NaN" => Some(f64::NAN),
+        "Infinity" | "+Infinity" => Some(f64::INFINITY),
+        "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
+// Interprets an unquoted value, falling back to a string when it isn't a valid number
+fn unquoted_value(value: &str) -> NBTTag {
+    match value {
+        "true" => return NBTTag::TagByte(1),
+        "false" => return NBTTag::TagByte(0),
+        _ => {}
+    }
+
+    let (number, suffix) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], Some(c.to_ascii_lowercase())),
+        _ => (value, None),
+    };
+
+    let tag = match suffix {
+        Some('b') if is_integer(number) => number.parse().ok().map(NBTTag::TagByte),
+        Some('s') if is_integer(number) => number.parse().ok().map(NBTTag::TagShort),
+        Some('l') if is_integer(number) => number.parse().ok().map(NBTTag::TagLong),
+        Some('f') if is_float(number, true) => number.parse().ok().map(NBTTag::TagFloat),
+        Some('d') if is_float(number, true) => number.parse().ok().map(NBTTag::TagDouble),
+        Some('f') => special_float(number).map(|value| NBTTag::TagFloat(value as f32)),
+        Some('d') => special_float(number).map(NBTTag::TagDouble),
+        None if is_integer(number) => number.parse().ok().map(NBTTag::TagInt),
+        None if is_float(number, false) => number.parse().ok().map(NBTTag::TagDouble),
+        _ => None,
+    };
+
+    tag.unwrap_or_else(|| NBTTag::TagString(value.to_owned()))
+}
+
+// Matches [-+]?(0|[1-9][0-9]*)
+fn is_integer(value: &str) -> bool {
+    let digits = value.trim_start_matches(['-', '+']);
+
+    value.len() - digits.len() <= 1 && !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) &&
+        (digits == "0" || !digits.starts_with('0'))
+}
+
+// Matches [-+]?([0-9]+[.]?|[0-9]*[.][0-9]+)(e[-+]?[0-9]+)?, where the dot or exponent is required
+// unless the value has a suffix
+fn is_float(value: &str, suffixed: bool) -> bool {
+    let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+
+    let (whole, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+
+    let mantissa_valid = all_digits(whole) && fraction.is_none_or(all_digits) &&
+        (!whole.is_empty() || fraction.is_some_and(|f| !f.is_empty()));
+
+    let exponent_valid = exponent.is_none_or(|e| {
+        let digits = e.strip_prefix(['-', '+']).unwrap_or(e);
+        !digits.is_empty() && all_digits(digits)
+    });
+
+    mantissa_valid && exponent_valid && (suffixed || fraction.is_some() || exponent.is_some())
+}
+
+#[test]
+fn test_from_snbt_arrays() {
+    assert_eq!(from_snbt("[I;]").unwrap(), NBTTag::TagIntArray(vec![]));
+    assert_eq!(from_snbt("[I; 1, 2, 3]").unwrap(), NBTTag::TagIntArray(vec![1, 2, 3]));
+    assert_eq!(from_snbt("[L;1l,-2L]").unwrap(), NBTTag::TagLongArray(vec![1, -2]));
+    assert_eq!(from_snbt("[B;1b,2b]").unwrap(), NBTTag::TagByteArray(vec![1, 2]));
+    assert_eq!(from_snbt("[1,2,3]").unwrap(),
+               NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(2), NBTTag::TagInt(3)]));
+    assert_eq!(from_snbt("[I,J]").unwrap(),
+               NBTTag::TagList(vec![NBTTag::TagString("I".to_owned()), NBTTag::TagString("J".to_owned())]));
+
+    assert!(from_snbt("[B;1,2]").is_err());
+    assert!(from_snbt("[1,2b]").is_err());
+}
+
+#[test]
+fn test_from_snbt_values() {
+    let tag = from_snbt(r#"{id: "minecraft:stone", Count: 64b, 'Damage': 1s, tick: 5L, x: 0.5f, y: 64.0, z: 1e3d,
+                           flag: true, name: Steve, quote: "say \"hi\"", num: 012, empty: {}, list: []}"#).unwrap();
+
+    let mut expected = IndexMap::new();
+    expected.insert("id".to_owned(), NBTTag::TagString("minecraft:stone".to_owned()));
+    expected.insert("Count".to_owned(), NBTTag::TagByte(64));
+    expected.insert("Damage".to_owned(), NBTTag::TagShort(1));
+    expected.insert("tick".to_owned(), NBTTag::TagLong(5));
+    expected.insert("x".to_owned(), NBTTag::TagFloat(0.5));
+    expected.insert("y".to_owned(), NBTTag::TagDouble(64.0));
+    expected.insert("z".to_owned(), NBTTag::TagDouble(1000.0));
+    expected.insert("flag".to_owned(), NBTTag::TagByte(1));
+    expected.insert("name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    expected.insert("quote".to_owned(), NBTTag::TagString("say \"hi\"".to_owned()));
+    expected.insert("num".to_owned(), NBTTag::TagString("012".to_owned()));
+    expected.insert("empty".to_owned(), NBTTag::TagCompound(IndexMap::new()));
+    expected.insert("list".to_owned(), NBTTag::TagList(vec![]));
+
+    assert_eq!(tag, NBTTag::TagCompound(expected));
+    assert_eq!(from_snbt("300b").unwrap(), NBTTag::TagString("300b".to_owned()));
+}
+
+#[test]
+fn test_from_snbt_errors() {
+    assert_eq!(from_snbt("{a:1,}").unwrap_err().to_string(), "Invalid SNBT at position 5: Expected key");
+    assert_eq!(from_snbt("{a:1} x").unwrap_err().to_string(), "Invalid SNBT at position 6: Trailing data after value");
+    assert!(from_snbt("\"abc").is_err());
+    assert!(from_snbt("[1 2]").is_err());
+}
+
+#[test]
+fn test_escape_snbt_string() {
+    assert_eq!(escape_snbt_string("plain"), "\"plain\"");
+    assert_eq!(escape_snbt_string("say \"hi\""), "'say \"hi\"'");
+    assert_eq!(escape_snbt_string("it's"), "\"it's\"");
+    assert_eq!(escape_snbt_string("\"it's\""), "'\"it\\'s\"'");
+    assert_eq!(escape_snbt_string("a\\b"), "\"a\\\\b\"");
+    assert_eq!(escape_snbt_string("line\nbreak\u{1}"), "\"line\\nbreak\\x01\"");
+
+    for value in ["say \"hi\"", "it's", "\"it's\"", "a\\b", "line\nbreak\ttab\u{1}\u{7f}"].iter() {
+        assert_eq!(from_snbt(&escape_snbt_string(value)).unwrap(), NBTTag::TagString(value.to_string()));
+    }
+}
+
+#[test]
+fn test_to_snbt_floats() {
+    assert_eq!(to_snbt(&NBTTag::TagDouble(64.0)), "64.0d");
+    assert_eq!(to_snbt(&NBTTag::TagFloat(-3.0)), "-3.0f");
+    assert_eq!(to_snbt(&NBTTag::TagFloat(1e20)), "1e20f");
+    assert_eq!(to_snbt(&NBTTag::TagDouble(f64::NAN)), "NaNd");
+    assert_eq!(to_snbt(&NBTTag::TagFloat(f32::INFINITY)), "Infinityf");
+    assert_eq!(to_snbt(&NBTTag::TagDouble(f64::NEG_INFINITY)), "-Infinityd");
+
+    for tag in &[NBTTag::TagDouble(64.0), NBTTag::TagFloat(1e20), NBTTag::TagDouble(f64::NAN),
+                 NBTTag::TagFloat(f32::NEG_INFINITY), NBTTag::TagDouble(-0.0)] {
+        assert_eq!(&from_snbt(&to_snbt(tag)).unwrap(), tag);
+    }
+
+    assert_eq!(from_snbt("NaN").unwrap(), NBTTag::TagString("NaN".to_owned()));
+}
+
+#[test]
+fn test_snbt_quoted_keys() {
+    let tag = from_snbt(r#"{"say \"hi\"":1,'it\'s':2,"a:b c":3,"":4,plain:5}"#).unwrap();
+    let keys: Vec<&str> = tag.compound_iter().unwrap().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, vec!["say \"hi\"", "it's", "a:b c", "", "plain"]);
+
+    let snbt = to_snbt(&tag);
+    assert_eq!(snbt, r#"{'say "hi"':1,"it's":2,"a:b c":3,"":4,plain:5}"#);
+
+    let read = from_snbt(&snbt).unwrap();
+    assert!(read.compound_iter().unwrap().map(|(key, _)| key.as_str()).eq(keys));
+    assert_eq!(read, tag);
+
+    assert!(from_snbt("{:1}").is_err());
+}
+
+#[test]
+fn test_to_snbt() {
+    let mut tag = NBTTag::TagCompound(IndexMap::new());
+    tag.insert("id", NBTTag::TagString("minecraft:stone".to_owned()));
+    tag.insert("Count", NBTTag::TagByte(1));
+    tag.insert("odd key", NBTTag::TagList(vec![NBTTag::TagShort(2), NBTTag::TagShort(3)]));
+    tag.insert("pos", NBTTag::TagDouble(64.0));
+    tag.insert("f", NBTTag::TagFloat(0.5));
+    tag.insert("ticks", NBTTag::TagLongArray(vec![1, -2]));
+    tag.insert("bytes", NBTTag::TagByteArray(vec![]));
+
+    let snbt = to_snbt(&tag);
+
+    assert_eq!(snbt, r#"{id:"minecraft:stone",Count:1b,"odd key":[2s,3s],pos:64.0d,f:0.5f,ticks:[L;1L,-2L],bytes:[B;]}"#);
+    assert_eq!(from_snbt(&snbt).unwrap(), tag);
+}
+
+#[test]
+fn test_from_snbt_rejects_deep_nesting() {
+    assert!(matches!(from_snbt(&"[".repeat(200_000)), Err(NBTError::InvalidSnbt { msg: "too deeply nested", .. })));
+    assert!(matches!(from_snbt(&"{a:".repeat(200_000)), Err(NBTError::InvalidSnbt { msg: "too deeply nested", .. })));
+
+    let nested = format!("{}{}", "[".repeat(MAX_DEPTH + 1), "]".repeat(MAX_DEPTH + 1));
+    assert!(from_snbt(&nested).is_ok());
+}
@@ -0,0 +1,75 @@
+use write::get_tag_id;
+use NBTTag;
+
+// Summary of the contents of a tree, see NBTTag::stats
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NbtStats {
+    // Number of tags of each type, indexed by tag id
+    pub counts: [usize; 13],
+    // Total length in bytes of the TagStrings and compound keys
+    pub string_bytes: usize,
+    // How deeply the most nested tag is nested in lists and compounds, 0 for the root itself
+    pub max_depth: usize,
+    // Length of the longest list or array
+    pub max_len: usize,
+}
+
+impl NbtStats {
+    // Total number of tags, including the root
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl NBTTag {
+    // Counts the tags of the tree in a single pass
+    pub fn stats(&self) -> NbtStats {
+        let mut stats = NbtStats::default();
+        collect(self, 0, &mut stats);
+        stats
+    }
+}
+
+fn collect(tag: &NBTTag, depth: usize, stats: &mut NbtStats) {
+    stats.counts[get_tag_id(tag) as usize] += 1;
+    stats.max_depth = stats.max_depth.max(depth);
+
+    match *tag {
+        NBTTag::TagByteArray(ref value) => stats.max_len = stats.max_len.max(value.len()),
+        NBTTag::TagString(ref value) => stats.string_bytes += value.len(),
+        NBTTag::TagList(ref value) => {
+            stats.max_len = stats.max_len.max(value.len());
+
+            for elem in value {
+                collect(elem, depth + 1, stats);
+            }
+        }
+        NBTTag::TagCompound(ref value) => {
+            for (key, elem) in value {
+                stats.string_bytes += key.len();
+                collect(elem, depth + 1, stats);
+            }
+        }
+        NBTTag::TagIntArray(ref value) => stats.max_len = stats.max_len.max(value.len()),
+        NBTTag::TagLongArray(ref value) => stats.max_len = stats.max_len.max(value.len()),
+        _ => (),
+    }
+}
+
+#[test]
+fn test_stats() {
+    use from_snbt;
+
+    let tag = from_snbt("{name:\"Steve\",Pos:[1.0d,2.0d,3.0d],Inventory:[{id:\"stone\"}],Data:[I;1,2,3,4]}").unwrap();
+    let stats = tag.stats();
+
+    assert_eq!(stats.counts[10], 2);
+    assert_eq!(stats.counts[9], 2);
+    assert_eq!(stats.counts[6], 3);
+    assert_eq!(stats.counts[8], 2);
+    assert_eq!(stats.counts[11], 1);
+    assert_eq!(stats.total(), 10);
+    assert_eq!(stats.string_bytes, "nameStevePosInventoryidstoneData".len());
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(stats.max_len, 4);
+}
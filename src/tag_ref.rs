@@ -0,0 +1,63 @@
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use NBTTag;
+
+// A tag borrowing its names, strings and byte arrays from the buffer it was read from, as read by
+// read::read_nbt_ref. Names and strings are owned when they were decoded from modified UTF-8.
+// Compound entries are kept in a Vec in the order they were read, which also avoids hashing their
+// keys.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NBTTagRef<'a> {
+    TagEnd,
+    TagByte(i8),
+    TagShort(i16),
+    TagInt(i32),
+    TagLong(i64),
+    TagFloat(f32),
+    TagDouble(f64),
+    // The raw bytes of the array, each of which is an i8
+    TagByteArray(&'a [u8]),
+    TagString(Cow<'a, str>),
+    TagList(Vec<NBTTagRef<'a>>),
+    TagCompound(Vec<(Cow<'a, str>, NBTTagRef<'a>)>),
+    TagIntArray(Vec<i32>),
+    TagLongArray(Vec<i64>),
+}
+
+impl<'a> NBTTagRef<'a> {
+    // Copies the tag into an owned NBTTag
+    pub fn to_owned(&self) -> NBTTag {
+        match *self {
+            NBTTagRef::TagEnd => NBTTag::TagEnd,
+            NBTTagRef::TagByte(value) => NBTTag::TagByte(value),
+            NBTTagRef::TagShort(value) => NBTTag::TagShort(value),
+            NBTTagRef::TagInt(value) => NBTTag::TagInt(value),
+            NBTTagRef::TagLong(value) => NBTTag::TagLong(value),
+            NBTTagRef::TagFloat(value) => NBTTag::TagFloat(value),
+            NBTTagRef::TagDouble(value) => NBTTag::TagDouble(value),
+            NBTTagRef::TagByteArray(value) => NBTTag::TagByteArray(value.iter().map(|&byte| byte as i8).collect()),
+            NBTTagRef::TagString(ref value) => NBTTag::TagString(value.as_ref().to_owned()),
+            NBTTagRef::TagList(ref value) => NBTTag::TagList(value.iter().map(NBTTagRef::to_owned).collect()),
+            NBTTagRef::TagCompound(ref value) => {
+                let mut map = IndexMap::default();
+
+                for (key, tag) in value {
+                    map.insert(key.as_ref().to_owned(), tag.to_owned());
+                }
+
+                NBTTag::TagCompound(map)
+            }
+            NBTTagRef::TagIntArray(ref value) => NBTTag::TagIntArray(value.clone()),
+            NBTTagRef::TagLongArray(ref value) => NBTTag::TagLongArray(value.clone()),
+        }
+    }
+
+    // Looks up an entry of a TagCompound. This is O(n) over the entries.
+    pub fn get(&self, key: &str) -> Option<&NBTTagRef<'a>> {
+        if let NBTTagRef::TagCompound(ref entries) = *self {
+            return entries.iter().find(|entry| entry.0 == key).map(|entry| &entry.1);
+        }
+
+        None
+    }
+}
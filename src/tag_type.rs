@@ -0,0 +1,100 @@
+use NBTTag;
+use TAG_NAMES;
+
+// The type of a tag, with the id it is written with as discriminant
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub enum TagType {
+    End = 0,
+    Byte = 1,
+    Short = 2,
+    Int = 3,
+    Long = 4,
+    Float = 5,
+    Double = 6,
+    ByteArray = 7,
+    String = 8,
+    List = 9,
+    Compound = 10,
+    IntArray = 11,
+    LongArray = 12,
+}
+
+impl TagType {
+    // The type with the given id, or None for ids above 12
+    pub fn from_u8(id: u8) -> Option<TagType> {
+        match id {
+            0 => Some(TagType::End),
+            1 => Some(TagType::Byte),
+            2 => Some(TagType::Short),
+            3 => Some(TagType::Int),
+            4 => Some(TagType::Long),
+            5 => Some(TagType::Float),
+            6 => Some(TagType::Double),
+            7 => Some(TagType::ByteArray),
+            8 => Some(TagType::String),
+            9 => Some(TagType::List),
+            10 => Some(TagType::Compound),
+            11 => Some(TagType::IntArray),
+            12 => Some(TagType::LongArray),
+            _ => None,
+        }
+    }
+
+    // The name of the type, e.g. "TAG_String"
+    pub fn name(self) -> &'static str {
+        TAG_NAMES[self as usize]
+    }
+}
+
+impl From<TagType> for u8 {
+    fn from(tag_type: TagType) -> u8 {
+        tag_type as u8
+    }
+}
+
+impl NBTTag {
+    pub fn tag_type(&self) -> TagType {
+        match *self {
+            NBTTag::TagEnd => TagType::End,
+            NBTTag::TagByte(_) => TagType::Byte,
+            NBTTag::TagShort(_) => TagType::Short,
+            NBTTag::TagInt(_) => TagType::Int,
+            NBTTag::TagLong(_) => TagType::Long,
+            NBTTag::TagFloat(_) => TagType::Float,
+            NBTTag::TagDouble(_) => TagType::Double,
+            NBTTag::TagByteArray(_) => TagType::ByteArray,
+            NBTTag::TagString(_) => TagType::String,
+            NBTTag::TagList(_) => TagType::List,
+            NBTTag::TagCompound(_) => TagType::Compound,
+            NBTTag::TagIntArray(_) => TagType::IntArray,
+            NBTTag::TagLongArray(_) => TagType::LongArray,
+        }
+    }
+
+    // The type of the elements of a TagList, taken from its first element. Empty lists have End,
+    // which is also the element type they are written with. None if this is not a list.
+    pub fn list_element_type(&self) -> Option<TagType> {
+        match *self {
+            NBTTag::TagList(ref list) => Some(list.first().map_or(TagType::End, NBTTag::tag_type)),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_tag_type() {
+    for id in 0..13 {
+        assert_eq!(TagType::from_u8(id).map(u8::from), Some(id));
+    }
+
+    assert_eq!(TagType::from_u8(13), None);
+    assert_eq!(NBTTag::TagString(String::new()).tag_type(), TagType::String);
+    assert_eq!(TagType::IntArray.name(), "TAG_Int_Array");
+}
+
+#[test]
+fn test_list_element_type() {
+    assert_eq!(NBTTag::TagList(vec![NBTTag::TagShort(1), NBTTag::TagShort(2)]).list_element_type(), Some(TagType::Short));
+    assert_eq!(NBTTag::TagList(Vec::new()).list_element_type(), Some(TagType::End));
+    assert_eq!(NBTTag::TagIntArray(vec![1]).list_element_type(), None);
+}
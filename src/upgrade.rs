@@ -0,0 +1,93 @@
+// Upgrading of data written by older versions of Minecraft, in the way of its DataFixerUpper. Every
+// fix is registered with the DataVersion that introduced the change it makes, and is run on data
+// with a lower DataVersion, in order of version.
+
+use error::NBTError;
+use NBTTag;
+
+// The DataVersion of 20w12a, from which entities store their UUID as a single TagIntArray
+pub const UUID_INT_ARRAY_VERSION: i32 = 2514;
+
+type Fix = Box<dyn Fn(&mut NBTTag) -> Result<(), NBTError>>;
+
+#[derive(Default)]
+pub struct Upgrader {
+    fixes: Vec<(i32, Fix)>,
+}
+
+impl Upgrader {
+    pub fn new() -> Upgrader {
+        Upgrader::default()
+    }
+
+    // Registers a fix that upgrades data with a DataVersion below version to version. Fixes of the
+    // same version run in the order they were registered.
+    pub fn register<F>(mut self, version: i32, fix: F) -> Upgrader
+        where F: Fn(&mut NBTTag) -> Result<(), NBTError> + 'static
+    {
+        let index = self.fixes.iter().position(|&(other, _)| other > version).unwrap_or(self.fixes.len());
+        self.fixes.insert(index, (version, Box::new(fix)));
+        self
+    }
+
+    // Runs the fixes that are newer than the DataVersion of a compound, which counts as 0 when it
+    // is missing, like in files from before 1.9. DataVersion is then set to the version of the last
+    // fix that ran, and returned. A fix that fails stops the upgrade, with DataVersion set to the
+    // version of the last fix that succeeded.
+    pub fn run(&self, tag: &mut NBTTag) -> Result<i32, NBTError> {
+        let mut data_version = match tag.get("DataVersion") {
+            Some(&NBTTag::TagInt(version)) => version,
+            Some(other) => return Err(NBTError::UnexpectedType { expected: 3, found: other.tag_type().into() }),
+            None if matches!(*tag, NBTTag::TagCompound(_)) => 0,
+            None => return Err(NBTError::UnexpectedType { expected: 10, found: tag.tag_type().into() }),
+        };
+        let initial_version = data_version;
+
+        let mut result = Ok(());
+
+        for &(version, ref fix) in self.fixes.iter().filter(|&&(version, _)| version > initial_version) {
+            result = fix(tag);
+
+            if result.is_err() {
+                break;
+            }
+
+            data_version = version;
+        }
+
+        if data_version != initial_version {
+            tag.insert("DataVersion", NBTTag::TagInt(data_version));
+        }
+
+        result.map(|()| data_version)
+    }
+}
+
+// Replaces the UUIDMost and UUIDLeast of an entity with UUID, like Minecraft did in 20w12a. Register
+// it at UUID_INT_ARRAY_VERSION.
+pub fn entity_uuid_fix(entity: &mut NBTTag) -> Result<(), NBTError> {
+    entity.upgrade_uuid_fields("UUIDMost", "UUIDLeast", "UUID");
+    Ok(())
+}
+
+#[test]
+fn test_upgrader() {
+    use from_snbt;
+
+    let upgrader = Upgrader::new()
+        .register(2000, |tag| tag.rename_key("Motion", "motion"))
+        .register(UUID_INT_ARRAY_VERSION, entity_uuid_fix)
+        .register(1500, |tag| tag.rename_key("id", "Id"));
+
+    let mut entity = from_snbt("{DataVersion:1700,id:\"pig\",Motion:[0.0d],UUIDMost:1L,UUIDLeast:2L}").unwrap();
+    assert_eq!(upgrader.run(&mut entity).unwrap(), UUID_INT_ARRAY_VERSION);
+    assert_eq!(entity, from_snbt("{DataVersion:2514,id:\"pig\",motion:[0.0d],UUID:[I;0,1,0,2]}").unwrap());
+
+    // Nothing is left to run once the data is up to date
+    assert_eq!(upgrader.run(&mut entity).unwrap(), UUID_INT_ARRAY_VERSION);
+
+    // The fix of 2000 fails, as the key has already been renamed
+    let mut entity = from_snbt("{id:\"pig\",motion:[0.0d]}").unwrap();
+    assert!(upgrader.run(&mut entity).is_err());
+    assert_eq!(entity.get("DataVersion"), Some(&NBTTag::TagInt(1500)));
+}
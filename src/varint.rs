@@ -0,0 +1,133 @@
+// LEB128 variable length integers, 7 bits per byte with the high bit set on all bytes but the last.
+// Java Edition encodes negative numbers in two's complement, so they always take the maximum number
+// of bytes. Bedrock Edition zigzag encodes signed numbers first, so small negative numbers stay
+// short.
+
+use error::NBTError;
+
+// Reads an int the way Java Edition encodes it, returning it and the number of bytes it took up
+pub fn read_varint(bytes: &[u8]) -> Result<(i32, usize), NBTError> {
+    read_unsigned(bytes, 5).map(|(value, len)| (value as u32 as i32, len))
+}
+
+pub fn read_varlong(bytes: &[u8]) -> Result<(i64, usize), NBTError> {
+    read_unsigned(bytes, 10).map(|(value, len)| (value as i64, len))
+}
+
+// Reads a zigzag encoded int, like Bedrock Edition writes them
+pub fn read_zigzag_varint(bytes: &[u8]) -> Result<(i32, usize), NBTError> {
+    read_unsigned(bytes, 5).map(|(value, len)| (((value >> 1) as i32) ^ -((value & 1) as i32), len))
+}
+
+pub fn read_zigzag_varlong(bytes: &[u8]) -> Result<(i64, usize), NBTError> {
+    read_unsigned(bytes, 10).map(|(value, len)| (((value >> 1) as i64) ^ -((value & 1) as i64), len))
+}
+
+pub fn encode_varint(value: i32) -> Vec<u8> {
+    encode_unsigned(u64::from(value as u32))
+}
+
+pub fn encode_varlong(value: i64) -> Vec<u8> {
+    encode_unsigned(value as u64)
+}
+
+pub fn encode_zigzag_varint(value: i32) -> Vec<u8> {
+    encode_unsigned(u64::from(((value << 1) ^ (value >> 31)) as u32))
+}
+
+pub fn encode_zigzag_varlong(value: i64) -> Vec<u8> {
+    encode_unsigned(((value << 1) ^ (value >> 63)) as u64)
+}
+
+fn read_unsigned(bytes: &[u8], max_len: usize) -> Result<(u64, usize), NBTError> {
+    let mut value: u64 = 0;
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i == max_len {
+            return Err(NBTError::InvalidValue(format!("Varint is longer than {} bytes", max_len)));
+        }
+
+        value |= u64::from(byte & 0x7F) << (7 * i);
+
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+
+    if bytes.len() >= max_len {
+        return Err(NBTError::InvalidValue(format!("Varint is longer than {} bytes", max_len)));
+    }
+
+    Err(NBTError::Parse { offset: bytes.len(), tag: None })
+}
+
+fn encode_unsigned(mut value: u64) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            output.push(byte);
+            return output;
+        }
+
+        output.push(byte | 0x80);
+    }
+}
+
+#[test]
+fn test_varint() {
+    let cases: &[(i32, &[u8])] = &[
+        (0, &[0x00]),
+        (1, &[0x01]),
+        (127, &[0x7F]),
+        (128, &[0x80, 0x01]),
+        (255, &[0xFF, 0x01]),
+        (25565, &[0xDD, 0xC7, 0x01]),
+        (2097151, &[0xFF, 0xFF, 0x7F]),
+        (2147483647, &[0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+        (-1, &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+        (-2147483648, &[0x80, 0x80, 0x80, 0x80, 0x08]),
+    ];
+
+    for &(value, bytes) in cases {
+        assert_eq!(encode_varint(value), bytes);
+        assert_eq!(read_varint(bytes).unwrap(), (value, bytes.len()));
+    }
+
+    assert_eq!(read_varint(&[0xDD, 0xC7, 0x01, 0x05]).unwrap(), (25565, 3));
+    assert!(read_varint(&[0x80, 0x80]).is_err());
+    assert!(read_varint(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]).is_err());
+}
+
+#[test]
+fn test_varlong() {
+    let cases: &[(i64, &[u8])] = &[
+        (2147483647, &[0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+        (9223372036854775807, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]),
+        (-1, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+        (-9223372036854775808, &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01]),
+    ];
+
+    for &(value, bytes) in cases {
+        assert_eq!(encode_varlong(value), bytes);
+        assert_eq!(read_varlong(bytes).unwrap(), (value, bytes.len()));
+    }
+}
+
+#[test]
+fn test_zigzag() {
+    let cases: &[(i32, &[u8])] = &[(0, &[0x00]), (-1, &[0x01]), (1, &[0x02]), (-64, &[0x7F]), (64, &[0x80, 0x01])];
+
+    for &(value, bytes) in cases {
+        assert_eq!(encode_zigzag_varint(value), bytes);
+        assert_eq!(read_zigzag_varint(bytes).unwrap(), (value, bytes.len()));
+        assert_eq!(encode_zigzag_varlong(i64::from(value)), bytes);
+        assert_eq!(read_zigzag_varlong(bytes).unwrap(), (i64::from(value), bytes.len()));
+    }
+
+    assert_eq!(read_zigzag_varint(&encode_zigzag_varint(i32::MIN)).unwrap().0, i32::MIN);
+    assert_eq!(read_zigzag_varlong(&encode_zigzag_varlong(i64::MAX)).unwrap().0, i64::MAX);
+}
@@ -0,0 +1,111 @@
+use indexmap::IndexMap;
+use path;
+use NBTTag;
+
+// Callbacks invoked by NBTTag::accept for every tag in a tree. Each method receives the path of
+// the tag it is called for, and does nothing unless overridden.
+pub trait Visitor {
+    fn visit_end(&mut self, _path: &str) {}
+    fn visit_byte(&mut self, _path: &str, _value: i8) {}
+    fn visit_short(&mut self, _path: &str, _value: i16) {}
+    fn visit_int(&mut self, _path: &str, _value: i32) {}
+    fn visit_long(&mut self, _path: &str, _value: i64) {}
+    fn visit_float(&mut self, _path: &str, _value: f32) {}
+    fn visit_double(&mut self, _path: &str, _value: f64) {}
+    fn visit_byte_array(&mut self, _path: &str, _value: &[i8]) {}
+    fn visit_string(&mut self, _path: &str, _value: &str) {}
+    // Called before the elements of the list are visited
+    fn visit_list(&mut self, _path: &str, _value: &[NBTTag]) {}
+    // Called before the entries of the compound are visited
+    fn visit_compound(&mut self, _path: &str, _value: &IndexMap<String, NBTTag>) {}
+    fn visit_int_array(&mut self, _path: &str, _value: &[i32]) {}
+    fn visit_long_array(&mut self, _path: &str, _value: &[i64]) {}
+}
+
+impl NBTTag {
+    // Walks the tree depth-first, calling the matching visitor method for each tag
+    pub fn accept<V: Visitor>(&self, visitor: &mut V) {
+        accept_at(self, "", visitor);
+    }
+}
+
+fn accept_at<V: Visitor>(tag: &NBTTag, current: &str, visitor: &mut V) {
+    match *tag {
+        NBTTag::TagEnd => visitor.visit_end(current),
+        NBTTag::TagByte(value) => visitor.visit_byte(current, value),
+        NBTTag::TagShort(value) => visitor.visit_short(current, value),
+        NBTTag::TagInt(value) => visitor.visit_int(current, value),
+        NBTTag::TagLong(value) => visitor.visit_long(current, value),
+        NBTTag::TagFloat(value) => visitor.visit_float(current, value),
+        NBTTag::TagDouble(value) => visitor.visit_double(current, value),
+        NBTTag::TagByteArray(ref value) => visitor.visit_byte_array(current, value),
+        NBTTag::TagString(ref value) => visitor.visit_string(current, value),
+        NBTTag::TagList(ref value) => {
+            visitor.visit_list(current, value);
+
+            for (index, elem) in value.iter().enumerate() {
+                accept_at(elem, &path::child_index(current, index), visitor);
+            }
+        }
+        NBTTag::TagCompound(ref value) => {
+            visitor.visit_compound(current, value);
+
+            for (key, elem) in value {
+                accept_at(elem, &path::child_key(current, key), visitor);
+            }
+        }
+        NBTTag::TagIntArray(ref value) => visitor.visit_int_array(current, value),
+        NBTTag::TagLongArray(ref value) => visitor.visit_long_array(current, value),
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct TypeCounter {
+    ints: usize,
+    strings: usize,
+    lists: usize,
+    compounds: usize,
+    paths: Vec<String>,
+}
+
+#[cfg(test)]
+impl Visitor for TypeCounter {
+    fn visit_int(&mut self, path: &str, _value: i32) {
+        self.ints += 1;
+        self.paths.push(path.to_owned());
+    }
+
+    fn visit_string(&mut self, path: &str, _value: &str) {
+        self.strings += 1;
+        self.paths.push(path.to_owned());
+    }
+
+    fn visit_list(&mut self, _path: &str, _value: &[NBTTag]) {
+        self.lists += 1;
+    }
+
+    fn visit_compound(&mut self, _path: &str, _value: &IndexMap<String, NBTTag>) {
+        self.compounds += 1;
+    }
+}
+
+#[test]
+fn test_visitor_counts_tags_by_type() {
+    let mut item = IndexMap::new();
+    item.insert("id".to_owned(), NBTTag::TagString("minecraft:stone".to_owned()));
+    item.insert("Count".to_owned(), NBTTag::TagInt(64));
+
+    let mut root = IndexMap::new();
+    root.insert("Name".to_owned(), NBTTag::TagString("Steve".to_owned()));
+    root.insert("Inventory".to_owned(), NBTTag::TagList(vec![NBTTag::TagCompound(item)]));
+
+    let mut counter = TypeCounter::default();
+    NBTTag::TagCompound(root).accept(&mut counter);
+
+    assert_eq!(counter.ints, 1);
+    assert_eq!(counter.strings, 2);
+    assert_eq!(counter.lists, 1);
+    assert_eq!(counter.compounds, 2);
+    assert_eq!(counter.paths, vec!["Name", "Inventory[0].id", "Inventory[0].Count"]);
+}
@@ -1,257 +1,640 @@
 use byteorder::{
     BigEndian,
+    ByteOrder,
+    LittleEndian,
     WriteBytesExt,
 };
+use compression::Compression;
+use error::NBTError;
+use read::StringEncoding;
+use std::borrow::Cow;
+use Endianness;
 use NBTTag;
+#[cfg(test)]
+use indexmap::IndexMap;
+#[cfg(test)]
+use read::read_headless_nbt;
 use std::vec::Vec;
 
-fn write_tag_byte(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagByte(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteOptions {
+    pub endianness: Endianness,
+    // The compression to write a file with, or None to use the compression of the file itself
+    pub compression: Option<Compression>,
+    // The name of the root tag of a file, or None to use the name of the file itself
+    pub root_name: Option<String>,
+    // Writes the entries of every compound sorted by key instead of in their stored order, for
+    // output that is the same for equal trees
+    pub sort_keys: bool,
+    // How strings and names are encoded. Mutf8 writes the modified UTF-8 of Java edition, which
+    // differs from UTF-8 only for the null character and characters outside the BMP.
+    pub string_encoding: StringEncoding,
+}
+
+// The defaults match Java edition files
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            endianness: Endianness::Big,
+            compression: None,
+            root_name: None,
+            sort_keys: false,
+            string_encoding: StringEncoding::Mutf8,
+        }
+    }
+}
 
-        output.write_i8(tag_value);
+const MAX_ARRAY_LEN: usize = i32::MAX as usize;
+const MAX_STRING_LEN: usize = u16::MAX as usize;
 
-        return Ok(output);
+// Lengths are written as an i32 for arrays and lists, and as a u16 for strings and names, so longer
+// values can't be represented
+fn check_len(len: usize, max: usize, kind: &'static str) -> Result<(), NBTError> {
+    if len > max {
+        return Err(NBTError::TooLong { kind, len, max });
     }
 
-    Err("Tag is not of type TagByte".to_owned())
+    Ok(())
 }
 
-fn write_tag_short(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagShort(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_byte(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
+    if let &NBTTag::TagByte(tag_value) = input {
+        output.write_i8(tag_value).unwrap();
 
-        output.write_i16::<BigEndian>(tag_value);
+        return Ok(());
+    }
 
-        return Ok(output);
+    Err(unexpected_type(1, input))
+}
+
+fn write_tag_short<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
+    if let &NBTTag::TagShort(tag_value) = input {
+        output.write_i16::<B>(tag_value).unwrap();
+
+        return Ok(());
     }
 
-    Err("Tag is not of type TagShort".to_owned())
+    Err(unexpected_type(2, input))
 }
 
-fn write_tag_int(input: &NBTTag) -> Result<Vec<u8>, String> {
+fn write_tag_int<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
     if let &NBTTag::TagInt(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+        output.write_i32::<B>(tag_value).unwrap();
 
-        output.write_i32::<BigEndian>(tag_value);
-
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagInt".to_owned())
+    Err(unexpected_type(3, input))
 }
 
-fn write_tag_long(input: &NBTTag) -> Result<Vec<u8>, String> {
+fn write_tag_long<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
     if let &NBTTag::TagLong(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
-
-        output.write_i64::<BigEndian>(tag_value);
+        output.write_i64::<B>(tag_value).unwrap();
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagLong".to_owned())
+    Err(unexpected_type(4, input))
 }
 
-fn write_tag_float(input: &NBTTag) -> Result<Vec<u8>, String> {
+fn write_tag_float<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
     if let &NBTTag::TagFloat(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+        output.write_f32::<B>(tag_value).unwrap();
 
-        output.write_f32::<BigEndian>(tag_value);
-
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagFloat".to_owned())
+    Err(unexpected_type(5, input))
 }
 
-fn write_tag_double(input: &NBTTag) -> Result<Vec<u8>, String> {
+fn write_tag_double<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
     if let &NBTTag::TagDouble(tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
-
-        output.write_f64::<BigEndian>(tag_value);
+        output.write_f64::<B>(tag_value).unwrap();
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagDouble".to_owned())
+    Err(unexpected_type(6, input))
 }
 
-fn write_tag_byte_array(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagByteArray(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_byte_array<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
+    if let NBTTag::TagByteArray(tag_value) = input {
+        check_len(tag_value.len(), MAX_ARRAY_LEN, "TagByteArray")?;
 
-        output.write_i32::<BigEndian>(tag_value.len() as i32);
+        output.reserve(4 + tag_value.len());
+        output.write_i32::<B>(tag_value.len() as i32).unwrap();
 
         for byte in tag_value {
-            output.write_i8(*byte);
+            output.write_i8(*byte).unwrap();
         }
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagByteArray".to_owned())
+    Err(unexpected_type(7, input))
 }
 
-fn write_tag_string(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagString(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
-
-        output.write_u16::<BigEndian>(tag_value.len() as u16);
-
-        output.extend_from_slice(tag_value.as_bytes());
+fn write_tag_string<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>, encoding: StringEncoding) -> Result<(), NBTError> {
+    if let NBTTag::TagString(tag_value) = input {
+        write_string::<B>(tag_value, output, encoding, "TagString")?;
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagString".to_owned())
+    Err(unexpected_type(8, input))
 }
 
-fn write_tag_compound(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagCompound(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_compound<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>, options: &WriteOptions) -> Result<(), NBTError> {
+    if let NBTTag::TagCompound(tag_value) = input {
+        // Only sorting needs the entries collected, the default writes them in place
+        if options.sort_keys {
+            let mut entries: Vec<(&String, &NBTTag)> = tag_value.iter().collect();
+            entries.sort_by_key(|entry| entry.0);
 
-        for tag in tag_value {
-            match write_tag(tag.1, true, true, Some(tag.0)) {
-                Ok(mut result) => output.append(&mut result),
-                Err(msg) => return Err(msg),
+            for tag in entries {
+                write_tag_generic::<B>(tag.1, output, true, true, Some(tag.0), options)?;
+            }
+        } else {
+            for tag in tag_value {
+                write_tag_generic::<B>(tag.1, output, true, true, Some(tag.0), options)?;
             }
         }
 
         output.push(0);
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagCompound".to_owned())
+    Err(unexpected_type(10, input))
 }
 
-fn write_tag_list(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagList(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_list<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>, options: &WriteOptions) -> Result<(), NBTError> {
+    if let NBTTag::TagList(tag_value) = input {
+        let elems_type = input.list_element_type().map_or(0, u8::from);
+        output.push(elems_type);
 
-        if tag_value.len() <= 0 {
-            return Err("Size of TagList is required to be bigger than 0".to_owned());
-        }
-
-        if let Some(tag_id) = get_tag_id(&tag_value[0]) {
-            output.push(tag_id);
-        } else {
-            return Err("Tag id not recognized".to_owned());
-        }
-
-        output.write_i32::<BigEndian>(tag_value.len() as i32);
+        check_len(tag_value.len(), MAX_ARRAY_LEN, "TagList")?;
+        output.write_i32::<B>(tag_value.len() as i32).unwrap();
 
+        // Only the type of the first element is written, so the others are required to match it
         for tag in tag_value {
-            match write_tag(tag, false, false, None) {
-                Ok(mut result) => output.append(&mut result),
-                Err(msg) => return Err(msg),
+            if get_tag_id(tag) != elems_type {
+                return Err(unexpected_type(elems_type, tag));
             }
+
+            write_tag_generic::<B>(tag, output, false, false, None, options)?;
         }
+
+        return Ok(());
     }
 
-    Err("Tag is not of type TagList".to_owned())
+    Err(unexpected_type(9, input))
 }
 
-fn write_tag_int_array(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagIntArray(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_int_array<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
+    if let NBTTag::TagIntArray(tag_value) = input {
+        check_len(tag_value.len(), MAX_ARRAY_LEN, "TagIntArray")?;
 
-        output.write_i32::<BigEndian>(tag_value.len() as i32);
+        output.reserve(4 + tag_value.len() * 4);
+        output.write_i32::<B>(tag_value.len() as i32).unwrap();
 
         for int in tag_value {
-            output.write_i32::<BigEndian>(*int);
+            output.write_i32::<B>(*int).unwrap();
         }
 
-        return Ok(output);
+        return Ok(());
     }
 
-    Err("Tag is not of type TagIntArray".to_owned())
+    Err(unexpected_type(11, input))
 }
 
-fn write_tag_long_array(input: &NBTTag) -> Result<Vec<u8>, String> {
-    if let &NBTTag::TagLongArray(ref tag_value) = input {
-        let mut output: Vec<u8> = Vec::new();
+fn write_tag_long_array<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>) -> Result<(), NBTError> {
+    if let NBTTag::TagLongArray(tag_value) = input {
+        check_len(tag_value.len(), MAX_ARRAY_LEN, "TagLongArray")?;
 
-        output.write_i32::<BigEndian>(tag_value.len() as i32);
+        output.reserve(4 + tag_value.len() * 8);
+        output.write_i32::<B>(tag_value.len() as i32).unwrap();
 
         for long in tag_value {
-            output.write_i64::<BigEndian>(*long);
+            output.write_i64::<B>(*long).unwrap();
         }
 
-        return Ok(output);
+        return Ok(());
+    }
+
+    Err(unexpected_type(12, input))
+}
+
+pub fn write_tag(input: &NBTTag, write_id: bool, write_name: bool, name: Option<&str>) -> Result<Vec<u8>, NBTError> {
+    let mut output = Vec::new();
+    write_tag_into(&mut output, input, write_id, write_name, name)?;
+    Ok(output)
+}
+
+// Like write_tag, but appends to output instead of allocating a new buffer, so one buffer can be
+// reused for many tags. output is left as it was if writing fails.
+pub fn write_tag_into(output: &mut Vec<u8>, input: &NBTTag, write_id: bool, write_name: bool,
+                      name: Option<&str>) -> Result<(), NBTError> {
+    let len = output.len();
+    let result = write_tag_generic::<BigEndian>(input, output, write_id, write_name, name, &WriteOptions::default());
+
+    if result.is_err() {
+        output.truncate(len);
     }
 
-    Err("Tag is not of type TagLongArray".to_owned())
+    result
 }
 
-pub fn write_tag(input: &NBTTag, write_id: bool, write_name: bool, name: Option<&String>) -> Result<Vec<u8>, String> {
-    let mut output: Vec<u8> = Vec::new();
+// write_tag, in the byte order and key order of options. The other options only apply to files.
+pub(crate) fn write_tag_with_options(input: &NBTTag, write_id: bool, write_name: bool, name: Option<&str>,
+                                     options: &WriteOptions) -> Result<Vec<u8>, NBTError> {
+    let mut output = Vec::new();
+
+    match options.endianness {
+        Endianness::Big => write_tag_generic::<BigEndian>(input, &mut output, write_id, write_name, name, options)?,
+        Endianness::Little => write_tag_generic::<LittleEndian>(input, &mut output, write_id, write_name, name, options)?,
+    }
+
+    Ok(output)
+}
+
+fn write_tag_generic<B: ByteOrder>(input: &NBTTag, output: &mut Vec<u8>, write_id: bool, write_name: bool,
+                                   name: Option<&str>, options: &WriteOptions) -> Result<(), NBTError> {
+    if let NBTTag::TagEnd = *input {
+        return Err(NBTError::InvalidValue("TagEnd cannot be written as a value".to_owned()));
+    }
 
     if write_id {
-        if let Some(tag_id) = get_tag_id(&input) {
-            output.push(tag_id);
+        output.push(get_tag_id(input));
+    }
+
+    if write_name {
+        if let Some(name_val) = name {
+            write_string::<B>(name_val, output, options.string_encoding, "tag name")?;
         }
     }
 
+    match *input {
+        NBTTag::TagByte(_) => write_tag_byte(input, output),
+        NBTTag::TagShort(_) => write_tag_short::<B>(input, output),
+        NBTTag::TagInt(_) => write_tag_int::<B>(input, output),
+        NBTTag::TagLong(_) => write_tag_long::<B>(input, output),
+        NBTTag::TagFloat(_) => write_tag_float::<B>(input, output),
+        NBTTag::TagDouble(_) => write_tag_double::<B>(input, output),
+        NBTTag::TagByteArray(_) => write_tag_byte_array::<B>(input, output),
+        NBTTag::TagString(_) => write_tag_string::<B>(input, output, options.string_encoding),
+        NBTTag::TagList(_) => write_tag_list::<B>(input, output, options),
+        NBTTag::TagCompound(_) => write_tag_compound::<B>(input, output, options),
+        NBTTag::TagIntArray(_) => write_tag_int_array::<B>(input, output),
+        NBTTag::TagLongArray(_) => write_tag_long_array::<B>(input, output),
+        NBTTag::TagEnd => unreachable!(),
+    }
+}
+
+// Writes just the value of a tag, omitting both its type id and name, like the elements of a list
+pub fn encode_value(tag: &NBTTag, endianness: Endianness) -> Result<Vec<u8>, NBTError> {
+    write_tag_with_options(tag, false, false, None, &WriteOptions { endianness, ..WriteOptions::default() })
+}
+
+// Writes the type id and value of a tag, omitting the name. The inverse of read_headless_nbt.
+pub fn write_headless(input: &NBTTag) -> Result<Vec<u8>, NBTError> {
+    write_tag(input, true, false, None)
+}
+
+// Writes a tag the way it appears as an entry of a compound or the root of a file: its type id, then
+// name as a length-prefixed string, then its value. Unlike a file, any type of tag may be written
+// and no compression is applied, so the output can be spliced into other NBT; options.compression
+// and options.root_name are ignored.
+pub fn write_named(name: &str, tag: &NBTTag, options: &WriteOptions) -> Result<Vec<u8>, NBTError> {
+    write_tag_with_options(tag, true, true, Some(name), options)
+}
+
+// Computes the number of bytes write_tag would produce for the same arguments, so with strings and
+// names in modified UTF-8
+pub(crate) fn serialized_len(input: &NBTTag, write_id: bool, write_name: bool, name: Option<&str>) -> usize {
+    let mut len = 0;
+
+    if write_id {
+        len += 1;
+    }
+
     if write_name {
         if let Some(name_val) = name {
-            if name_val.len() == 0 {
-                output.write_u16::<BigEndian>(0 as u16);
-            } else {
-                output.write_u16::<BigEndian>(name_val.len() as u16);
-            }
+            len += name_prefix_len(name_val);
+        }
+    }
 
-            output.extend_from_slice(name_val.as_bytes());
+    len + match *input {
+        NBTTag::TagEnd => 0,
+        NBTTag::TagByte(_) => 1,
+        NBTTag::TagShort(_) => 2,
+        NBTTag::TagInt(_) => 4,
+        NBTTag::TagLong(_) => 8,
+        NBTTag::TagFloat(_) => 4,
+        NBTTag::TagDouble(_) => 8,
+        NBTTag::TagByteArray(ref tag_value) => 4 + tag_value.len(),
+        NBTTag::TagString(ref tag_value) => name_prefix_len(tag_value),
+        NBTTag::TagList(ref tag_value) => {
+            5 + tag_value.iter().map(|tag| serialized_len(tag, false, false, None)).sum::<usize>()
         }
+        NBTTag::TagCompound(ref tag_value) => {
+            1 + tag_value.iter().map(|tag| serialized_len(tag.1, true, true, Some(tag.0))).sum::<usize>()
+        }
+        NBTTag::TagIntArray(ref tag_value) => 4 + tag_value.len() * 4,
+        NBTTag::TagLongArray(ref tag_value) => 4 + tag_value.len() * 8,
     }
+}
+
+// Number of bytes a name takes up when written: its u16 length followed by its bytes in modified
+// UTF-8, in which NUL takes two bytes and characters outside the BMP six
+pub fn name_prefix_len(name: &str) -> usize {
+    2 + name.len() + name.chars().map(|c| match c {
+        '\0' => 1,
+        c if c as u32 > 0xFFFF => 2,
+        _ => 0,
+    }).sum::<usize>()
+}
+
+// Writes a string or name as its u16 length followed by its bytes in the given encoding
+fn write_string<B: ByteOrder>(value: &str, output: &mut Vec<u8>, encoding: StringEncoding,
+                              kind: &'static str) -> Result<(), NBTError> {
+    let bytes = match encoding {
+        StringEncoding::Utf8 => Cow::Borrowed(value.as_bytes()),
+        StringEncoding::Mutf8 => encode_mutf8(value),
+    };
 
-    let mut tag_result: Vec<u8>;
+    check_len(bytes.len(), MAX_STRING_LEN, kind)?;
+    output.write_u16::<B>(bytes.len() as u16).unwrap();
+    output.extend_from_slice(&bytes);
 
-    match input {
-        &NBTTag::TagByte(_) => tag_result = write_tag_byte(&input)?,
-        &NBTTag::TagShort(_) => tag_result = write_tag_short(&input)?,
-        &NBTTag::TagInt(_) => tag_result = write_tag_int(&input)?,
-        &NBTTag::TagLong(_) => tag_result = write_tag_long(&input)?,
-        &NBTTag::TagFloat(_) => tag_result = write_tag_float(&input)?,
-        &NBTTag::TagDouble(_) => tag_result = write_tag_double(&input)?,
-        &NBTTag::TagByteArray(_) => tag_result = write_tag_byte_array(&input)?,
-        &NBTTag::TagString(_) => tag_result = write_tag_string(&input)?,
-        &NBTTag::TagList(_) => tag_result = write_tag_list(&input)?,
-        &NBTTag::TagCompound(_) => tag_result = write_tag_compound(&input)?,
-        &NBTTag::TagIntArray(_) => tag_result = write_tag_int_array(&input)?,
-        &NBTTag::TagLongArray(_) => tag_result = write_tag_long_array(&input)?,
-        _ => return Err("Tag type not matched".to_owned())
+    Ok(())
+}
+
+// Encodes a string in modified UTF-8: NUL as the two bytes C0 80 and characters outside the BMP as
+// the three byte encodings of both halves of their surrogate pair. Other strings are already valid
+// modified UTF-8 and are borrowed.
+fn encode_mutf8(value: &str) -> Cow<'_, [u8]> {
+    if !value.bytes().any(|byte| byte == 0 || byte >= 0xF0) {
+        return Cow::Borrowed(value.as_bytes());
     }
 
-    output.append(&mut tag_result);
+    let mut bytes = Vec::with_capacity(name_prefix_len(value) - 2);
 
-    Ok(output)
+    for c in value.chars() {
+        match c {
+            '\0' => bytes.extend_from_slice(&[0xC0, 0x80]),
+            c if c as u32 > 0xFFFF => {
+                for &unit in c.encode_utf16(&mut [0; 2]).iter() {
+                    bytes.extend_from_slice(&[0xE0 | (unit >> 12) as u8, 0x80 | (unit >> 6 & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8]);
+                }
+            }
+            c => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    Cow::Owned(bytes)
 }
 
-fn get_tag_id(tag: &NBTTag) -> Option<u8> {
-    match tag {
-        &NBTTag::TagByte(_) => Some(1),
-        &NBTTag::TagShort(_) => Some(2),
-        &NBTTag::TagInt(_) => Some(3),
-        &NBTTag::TagLong(_) => Some(4),
-        &NBTTag::TagFloat(_) => Some(5),
-        &NBTTag::TagDouble(_) => Some(6),
-        &NBTTag::TagByteArray(_) => Some(7),
-        &NBTTag::TagString(_) => Some(8),
-        &NBTTag::TagList(_) => Some(9),
-        &NBTTag::TagCompound(_) => Some(10),
-        &NBTTag::TagIntArray(_) => Some(11),
-        &NBTTag::TagLongArray(_) => Some(12),
-        _ => None,
+fn unexpected_type(expected: u8, found: &NBTTag) -> NBTError {
+    NBTError::UnexpectedType {
+        expected,
+        found: get_tag_id(found),
     }
 }
 
+// Every tag type has an id, including TagEnd (0), even though TagEnd can't be written as a value
+pub(crate) fn get_tag_id(tag: &NBTTag) -> u8 {
+    tag.tag_type() as u8
+}
+
 #[test]
 fn check_tag_matcher() {
     let input = NBTTag::TagString("Hello World!".to_owned());
     let result = get_tag_id(&input);
-    let expected = Some(8);
+    let expected = 8;
 
     assert_eq!(result, expected);
-}
\ No newline at end of file
+}
+#[test]
+fn test_serialized_len_matches_write_tag() {
+    let mut compound = IndexMap::new();
+    compound.insert("byte".to_owned(), NBTTag::TagByte(1));
+    compound.insert("short".to_owned(), NBTTag::TagShort(2));
+    compound.insert("int".to_owned(), NBTTag::TagInt(3));
+    compound.insert("long".to_owned(), NBTTag::TagLong(4));
+    compound.insert("float".to_owned(), NBTTag::TagFloat(5.0));
+    compound.insert("double".to_owned(), NBTTag::TagDouble(6.0));
+    compound.insert("bytes".to_owned(), NBTTag::TagByteArray(vec![1, 2, 3]));
+    compound.insert("string".to_owned(), NBTTag::TagString("Hello World!".to_owned()));
+    compound.insert("ints".to_owned(), NBTTag::TagIntArray(vec![1, 2]));
+    compound.insert("longs".to_owned(), NBTTag::TagLongArray(vec![1, 2, 3, 4]));
+    compound.insert("nested".to_owned(), NBTTag::TagCompound(IndexMap::new()));
+
+    let input = NBTTag::TagCompound(compound);
+    let name = "root";
+
+    assert_eq!(serialized_len(&input, true, true, Some(name)),
+               write_tag(&input, true, true, Some(name)).unwrap().len());
+    assert_eq!(serialized_len(&input, false, false, None),
+               write_tag(&input, false, false, None).unwrap().len());
+}
+
+#[test]
+fn test_write_headless() {
+    let mut compound = IndexMap::new();
+    compound.insert("a".to_owned(), NBTTag::TagString("b".to_owned()));
+
+    assert_eq!(write_headless(&NBTTag::TagCompound(compound)).unwrap(),
+               vec![0x0A, 0x08, 0x00, 0x01, 0x61, 0x00, 0x01, 0x62, 0x00]);
+}
+
+#[test]
+fn test_write_tag_into() {
+    let mut compound = IndexMap::new();
+    compound.insert("a".to_owned(), NBTTag::TagString("b".to_owned()));
+    let tags = [NBTTag::TagCompound(compound), NBTTag::TagIntArray(vec![1, 2]), NBTTag::TagShort(3)];
+
+    let mut output = vec![0xFF];
+    let mut expected = vec![0xFF];
+
+    for tag in &tags {
+        write_tag_into(&mut output, tag, true, true, Some("name")).unwrap();
+        expected.extend(write_tag(tag, true, true, Some("name")).unwrap());
+    }
+
+    assert_eq!(output, expected);
+
+    // A failed write leaves the previous contents alone
+    assert!(write_tag_into(&mut output, &NBTTag::TagList(vec![NBTTag::TagEnd]), true, false, None).is_err());
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_write_arrays_reserve_once() {
+    // An array written into an empty buffer reserves its whole length up front, instead of growing
+    // the buffer while writing its elements
+    let tags = [NBTTag::TagLongArray((0..16 * 256).collect()), NBTTag::TagIntArray((0..1000).collect())];
+
+    for tag in &tags {
+        let mut output = Vec::new();
+        write_tag_into(&mut output, tag, false, false, None).unwrap();
+
+        assert_eq!(output.len(), tag.serialized_len());
+        assert_eq!(output.capacity(), output.len());
+    }
+}
+
+#[test]
+fn test_write_named() {
+    assert_eq!(write_named("ab", &NBTTag::TagShort(1), &WriteOptions::default()).unwrap(), vec![0x02, 0x00, 0x02, 0x61, 0x62, 0x00, 0x01]);
+
+    let little = WriteOptions { endianness: Endianness::Little, ..WriteOptions::default() };
+    assert_eq!(write_named("", &NBTTag::TagShort(1), &little).unwrap(), vec![0x02, 0x00, 0x00, 0x01, 0x00]);
+
+    assert!(write_named("a", &NBTTag::TagEnd, &WriteOptions::default()).is_err());
+    assert!(write_named(&"a".repeat(70000), &NBTTag::TagByte(0), &WriteOptions::default()).is_err());
+}
+
+#[test]
+fn test_check_len() {
+    assert!(check_len(4, 4, "TagString").is_ok());
+    assert_eq!(check_len(5, 4, "TagString").unwrap_err().to_string(),
+               "Length 5 of TagString exceeds the maximum of 4");
+}
+
+#[test]
+fn test_write_string_too_long() {
+    let input = NBTTag::TagString("a".repeat(MAX_STRING_LEN + 1));
+
+    match write_tag(&input, false, false, None) {
+        Err(NBTError::TooLong { kind, len, max }) => {
+            assert_eq!((kind, len, max), ("TagString", MAX_STRING_LEN + 1, MAX_STRING_LEN));
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    assert!(write_tag(&NBTTag::TagByte(0), false, true, Some(&"a".repeat(MAX_STRING_LEN + 1))).is_err());
+}
+
+#[test]
+fn test_write_tag_end() {
+    assert_eq!(get_tag_id(&NBTTag::TagEnd), 0);
+    assert_eq!(write_tag(&NBTTag::TagEnd, true, false, None).unwrap_err().to_string(),
+               "TagEnd cannot be written as a value");
+}
+
+#[test]
+fn test_write_sorted_keys() {
+    let mut inner = IndexMap::new();
+    inner.insert("y".to_owned(), NBTTag::TagByte(1));
+    inner.insert("x".to_owned(), NBTTag::TagByte(2));
+    let mut a = NBTTag::TagCompound(IndexMap::new());
+    a.insert("b", NBTTag::TagList(vec![NBTTag::TagCompound(inner)]));
+    a.insert("a", NBTTag::TagInt(3));
+
+    let mut b = a.clone();
+    b.canonicalize();
+
+    let sorted = WriteOptions { sort_keys: true, ..WriteOptions::default() };
+    let bytes = write_tag_with_options(&a, true, true, Some(""), &sorted).unwrap();
+
+    assert_eq!(bytes, write_tag(&b, true, true, Some("")).unwrap());
+    assert_ne!(bytes, write_tag(&a, true, true, Some("")).unwrap());
+}
+
+#[test]
+fn test_write_empty_list() {
+    let mut compound = IndexMap::new();
+    compound.insert("a".to_owned(), NBTTag::TagList(vec![]));
+    let tag = NBTTag::TagCompound(compound);
+
+    let bytes = write_headless(&tag).unwrap();
+
+    assert_eq!(bytes, vec![0x0A, 0x09, 0x00, 0x01, 0x61, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    assert_eq!(bytes.len(), serialized_len(&tag, true, false, None));
+    assert_eq!(read_headless_nbt(&bytes).unwrap(), tag);
+}
+
+#[test]
+fn test_name_prefix_len() {
+    for name in &["", "Level", "é", "a\0b", "\u{1F600}"] {
+        let written = write_tag(&NBTTag::TagByte(0), false, true, Some(name)).unwrap();
+
+        assert_eq!(name_prefix_len(name), written.len() - 1);
+    }
+}
+
+#[test]
+fn test_encode_value() {
+    assert_eq!(encode_value(&NBTTag::TagInt(1), Endianness::Big).unwrap(), vec![0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(encode_value(&NBTTag::TagInt(1), Endianness::Little).unwrap(), vec![0x01, 0x00, 0x00, 0x00]);
+    assert_eq!(encode_value(&NBTTag::TagString("a".to_owned()), Endianness::Little).unwrap(),
+               vec![0x01, 0x00, 0x61]);
+    assert_eq!(encode_value(&NBTTag::TagList(vec![NBTTag::TagShort(2)]), Endianness::Big).unwrap(),
+               vec![0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02]);
+    assert!(encode_value(&NBTTag::TagEnd, Endianness::Big).is_err());
+}
+
+#[test]
+fn test_write_nested_lists_and_compounds() {
+    let mut compound = IndexMap::new();
+    compound.insert("a".to_owned(), NBTTag::TagByte(1));
+
+    let lists = NBTTag::TagList(vec![
+        NBTTag::TagList(vec![NBTTag::TagByte(1), NBTTag::TagByte(2)]),
+        NBTTag::TagList(vec![NBTTag::TagString("b".to_owned())]),
+    ]);
+    let compounds = NBTTag::TagList(vec![NBTTag::TagCompound(compound.clone()), NBTTag::TagCompound(compound)]);
+
+    // Elements of lists have neither id nor name, but compounds still end with TagEnd
+    assert_eq!(write_headless(&lists).unwrap(), vec![
+        0x09, 0x09, 0x00, 0x00, 0x00, 0x02,
+        0x01, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02,
+        0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x62,
+    ]);
+    assert_eq!(write_headless(&compounds).unwrap(), vec![
+        0x09, 0x0A, 0x00, 0x00, 0x00, 0x02,
+        0x01, 0x00, 0x01, 0x61, 0x01, 0x00,
+        0x01, 0x00, 0x01, 0x61, 0x01, 0x00,
+    ]);
+
+    for tag in &[lists, compounds] {
+        let bytes = write_headless(tag).unwrap();
+
+        assert_eq!(&read_headless_nbt(&bytes).unwrap(), tag);
+        assert_eq!(serialized_len(tag, true, false, None), bytes.len());
+    }
+}
+
+#[test]
+fn test_write_mixed_list() {
+    let list = NBTTag::TagList(vec![NBTTag::TagInt(1), NBTTag::TagInt(2), NBTTag::TagString("3".to_owned())]);
+
+    match write_headless(&list) {
+        Err(NBTError::UnexpectedType { expected, found }) => assert_eq!((expected, found), (3, 8)),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    let nested = NBTTag::TagList(vec![NBTTag::TagList(vec![NBTTag::TagByte(1), NBTTag::TagShort(2)])]);
+    assert!(write_headless(&nested).is_err());
+}
+
+#[test]
+fn test_write_mutf8() {
+    use read::{read_nbt_file, ReadOptions};
+
+    let mut file = NBTTag::TagCompound(IndexMap::new());
+    file.insert("a\0", NBTTag::TagString("\u{1F600}".to_owned()));
+    let bytes = write_tag(&file, true, true, Some("")).unwrap();
+
+    // The same bytes test_read_mutf8 reads
+    assert_eq!(bytes, vec![
+        0x0A, 0x00, 0x00, 0x08, 0x00, 0x03, 0x61, 0xC0, 0x80, 0x00, 0x06, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0x00
+    ]);
+    assert_eq!(read_nbt_file(&bytes, &ReadOptions::default()).unwrap().1.unwrap().root, file);
+    assert_eq!(serialized_len(&file, true, true, Some("")), bytes.len());
+
+    let utf8 = WriteOptions { string_encoding: StringEncoding::Utf8, ..WriteOptions::default() };
+    assert_eq!(write_tag_with_options(&NBTTag::TagString("a\0".to_owned()), false, false, None, &utf8).unwrap(),
+               vec![0x00, 0x02, 0x61, 0x00]);
+}
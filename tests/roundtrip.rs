@@ -0,0 +1,49 @@
+extern crate rust_nbt;
+
+use rust_nbt::file::NBTFile;
+use rust_nbt::NBTTag;
+
+// Uncompressed versions of the classic test files from the NBT specification
+const HELLO_WORLD: &[u8] = include_bytes!("data/hello_world.nbt");
+const BIGTEST: &[u8] = include_bytes!("data/bigtest.nbt");
+
+fn assert_round_trip(bytes: &[u8]) -> NBTFile {
+    let file = NBTFile::from_bytes(bytes).unwrap();
+
+    assert_eq!(file.as_bytes().unwrap(), bytes);
+    assert_eq!(file.serialized_len(), bytes.len());
+
+    file
+}
+
+#[test]
+fn test_hello_world_round_trip() {
+    let file = assert_round_trip(HELLO_WORLD);
+
+    assert_eq!(file.root_name, "hello world");
+    assert_eq!(file.get("name"), Some(&NBTTag::TagString("Bananrama".to_owned())));
+}
+
+#[test]
+fn test_bigtest_round_trip() {
+    let file = assert_round_trip(BIGTEST);
+
+    assert_eq!(file.root_name, "Level");
+    assert_eq!(file.root_compound().len(), 11);
+    assert_eq!(file.get("longTest"), Some(&NBTTag::TagLong(i64::MAX)));
+    assert_eq!(file.get("stringTest"),
+               Some(&NBTTag::TagString("HELLO WORLD THIS IS A TEST STRING \u{c5}\u{c4}\u{d6}!".to_owned())));
+
+    let nested = file.get("nested compound test").unwrap();
+    assert_eq!(nested.get("egg").and_then(|egg| egg.get("value")), Some(&NBTTag::TagFloat(0.5)));
+
+    let compounds = file.get("listTest (compound)").unwrap();
+    assert_eq!(compounds.get(1).and_then(|elem| elem.get("created-on")), Some(&NBTTag::TagLong(1264099775885)));
+
+    let longs = file.get("listTest (long)").unwrap();
+    assert_eq!(longs, &NBTTag::TagList((11..16).map(NBTTag::TagLong).collect()));
+
+    let key = "byteArrayTest (the first 1000 values of (n*n*255+n*7)%100, starting with n=0 (0, 62, 34, 16, 8, ...))";
+    let expected: Vec<i8> = (0..1000).map(|n: i32| ((n * n * 255 + n * 7) % 100) as i8).collect();
+    assert_eq!(file.get(key), Some(&NBTTag::TagByteArray(expected)));
+}